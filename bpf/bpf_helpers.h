@@ -33,6 +33,17 @@ static unsigned long long (*bpf_get_current_uid_gid)(void) =
 	(void *) BPF_FUNC_get_current_uid_gid;
 static int (*bpf_get_current_comm)(void *buf, int buf_size) =
 	(void *) BPF_FUNC_get_current_comm;
+static unsigned long long (*bpf_get_current_cgroup_id)(void) =
+	(void *) BPF_FUNC_get_current_cgroup_id;
+static unsigned long long (*bpf_get_current_task)(void) =
+	(void *) BPF_FUNC_get_current_task;
+static int (*bpf_probe_read_str)(void *dst, int size, const void *unsafe_ptr) =
+	(void *) BPF_FUNC_probe_read_str;
+#if LINUX_VERSION_CODE >= KERNEL_VERSION(5, 8, 0)
+static long (*bpf_ringbuf_output)(void *ringbuf, void *data, unsigned long long size,
+				  unsigned long long flags) =
+	(void *) BPF_FUNC_ringbuf_output;
+#endif
 static int (*bpf_perf_event_read)(void *map, int index) =
 	(void *) BPF_FUNC_perf_event_read;
 static int (*bpf_clone_redirect)(void *ctx, int ifindex, int flags) =
@@ -93,7 +104,7 @@ static int (*bpf_l4_csum_replace)(void *ctx, int off, int from, int to, int flag
 static int (*bpf_skb_under_cgroup)(void *ctx, void *map, int index) =
 	(void *) BPF_FUNC_skb_under_cgroup;
 
-#if defined(__x86_64__)
+#if defined(__x86_64__) || defined(__TARGET_ARCH_x86)
 
 #define PT_REGS_PARM1(x) ((x)->di)
 #define PT_REGS_PARM2(x) ((x)->si)
@@ -119,7 +130,7 @@ static int (*bpf_skb_under_cgroup)(void *ctx, void *map, int index) =
 #define PT_REGS_SP(x) ((x)->gprs[15])
 #define PT_REGS_IP(x) ((x)->psw.addr)
 
-#elif defined(__aarch64__)
+#elif defined(__aarch64__) || defined(__TARGET_ARCH_arm64)
 
 #define PT_REGS_PARM1(x) ((x)->regs[0])
 #define PT_REGS_PARM2(x) ((x)->regs[1])
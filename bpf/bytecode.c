@@ -1,14 +1,67 @@
 #define KBUILD_MODNAME "bytecode"
+#ifdef CORE
+#include "vmlinux.h"
+
+#define KERNEL_VERSION(a, b, c) (((a) << 16) + ((b) << 8) + (c))
+#define LINUX_VERSION_CODE      KERNEL_VERSION(255, 255, 255)
+#define BPF_F_CURRENT_CPU       0xffffffffULL
+#define NULL                    ((void *) 0)
+#define __always_inline         inline __attribute__((always_inline))
+
+#if __BYTE_ORDER__ == __ORDER_LITTLE_ENDIAN__
+#define ntohs(x) __builtin_bswap16(x)
+#else
+#define ntohs(x) (x)
+#endif
+#else
 #include <linux/kconfig.h>
 #include <linux/bpf.h>
+#include <linux/in.h>
+#include <linux/in6.h>
+#include <linux/mm_types.h>
+#include <linux/nsproxy.h>
+#include <linux/sched.h>
+#include <linux/socket.h>
 #include <linux/tcp.h>
 #include <linux/version.h>
+#endif
 #include "bpf_helpers.h"
 
+#define VERSION 5
+
+#define FILENAME_SIZE 128
+#define ARGV_SIZE     256
+#define PREFIXES      16
+
+#define FILTER_ALLOW_PORTS  (1 << 0)
+#define FILTER_DENY_PORTS   (1 << 1)
+#define FILTER_INCLUDE      (1 << 2)
+#define FILTER_EXCLUDE      (1 << 3)
+#define FILTER_LOOPBACK     (1 << 4)
+
+#define PORT_ALLOW     1
+#define PORT_DENY      2
+#define PREFIX_INCLUDE 1
+#define PREFIX_EXCLUDE 2
+
+#ifndef AF_INET
+#define AF_INET 2
+#endif
+
+#ifndef AF_INET6
+#define AF_INET6 10
+#endif
+
+#ifndef CLONE_THREAD
+#define CLONE_THREAD 0x00010000
+#endif
+
 #ifndef LINUX_VERSION_CODE
 #pragma message("LINUX_VERSION_CODE not defined")
 #endif
 
+#define READ(dst, src) bpf_probe_read(&(dst), sizeof(dst), __builtin_preserve_access_index(&(src)))
+
 #define bpf_printk(fmt, ...)                             \
 ({                                                       \
     char _fmt[] = fmt;                                   \
@@ -21,11 +74,42 @@ enum kind {
     CONNECT,
     ACCEPT,
     CLOSE,
+    CONNECT6,
+    ACCEPT6,
+    CLOSE6,
+    UDP,
+    UDP6,
+    CONNECT_FAILED,
+    CONNECT6_FAILED,
+    STATE,
+    STATE6,
+    RETRANSMIT,
+    RETRANSMIT6,
+    SEND_RESET,
+    SEND_RESET6,
+    RECEIVE_RESET,
+    RECEIVE_RESET6,
+    FORK,
+};
+
+struct task {
+    u32  tid;
+    u32  uid;
+    u32  gid;
+    u32  __pad;
+    char comm[16];
 };
 
 struct header {
-    u32 kind;
-    u32 pid;
+    u16         version;
+    u16         size;
+    u32         length;
+    u32         kind;
+    u32         pid;
+    u64         ts;
+    u64         cgroup;
+    struct task task;
+    u64         netns;
 };
 
 struct sock4 {
@@ -36,6 +120,14 @@ struct sock4 {
     u32 dport;
 };
 
+struct sock6 {
+    u32 proto;
+    u8  saddr[16];
+    u32 sport;
+    u8  daddr[16];
+    u32 dport;
+};
+
 struct connect {
     struct header header;
     struct sock4  socket;
@@ -49,12 +141,177 @@ struct accept {
 struct close {
     struct header header;
     struct sock4  socket;
-    u32           rx;
-    u32           tx;
+    u64           rx;
+    u64           tx;
     u32           srtt;
     u32           retx;
+} __attribute__((packed));
+
+struct connect6 {
+    struct header header;
+    struct sock6  socket;
+};
+
+struct accept6 {
+    struct header header;
+    struct sock6  socket;
+};
+
+struct close6 {
+    struct header header;
+    struct sock6  socket;
+    u64           rx;
+    u64           tx;
+    u32           srtt;
+    u32           retx;
+} __attribute__((packed));
+
+struct connect_failed {
+    struct header header;
+    struct sock4  socket;
+    s32           error;
+};
+
+struct connect6_failed {
+    struct header header;
+    struct sock6  socket;
+    s32           error;
+};
+
+struct state {
+    struct header header;
+    struct sock4  socket;
+    u64           sk;
+    u64           start;
+    u32           old;
+    u32           new;
+    s32           error;
+} __attribute__((packed));
+
+struct state6 {
+    struct header header;
+    struct sock6  socket;
+    u64           sk;
+    u64           start;
+    u32           old;
+    u32           new;
+    s32           error;
+} __attribute__((packed));
+
+struct tcp_event {
+    struct header header;
+    struct sock4  socket;
+};
+
+struct tcp_event6 {
+    struct header header;
+    struct sock6  socket;
+};
+
+struct exec {
+    struct header header;
+    u32           ppid;
+    u32           argv_len;
+    char          filename[FILENAME_SIZE];
+    char          argv[ARGV_SIZE];
 };
 
+struct fork {
+    struct header header;
+    u32           ppid;
+};
+
+struct live {
+    u32          pid;
+    struct sock6 socket;
+    u64          cgroup;
+    u64          netns;
+    u64          start;
+    u64          rx;
+    u64          tx;
+    u32          srtt;
+    u32          retx;
+    struct task  task;
+};
+
+struct connect_call {
+    struct sock     *sk;
+    struct sockaddr *addr;
+    u64              start;
+};
+
+struct msg_call {
+    struct sock   *sk;
+    struct msghdr *msg;
+};
+
+struct udp_key {
+    u32          pid;
+    struct sock6 socket;
+};
+
+struct udp_flow {
+    u64         cgroup;
+    u64         netns;
+    u64         ts;
+    u64         rx;
+    u64         tx;
+    u32         rx_pkts;
+    u32         tx_pkts;
+    struct task task;
+};
+
+struct sock_info {
+    u16             family;
+    u8              state;
+    u16             num;
+    u16             dport;
+    u32             rcv_saddr;
+    u32             daddr;
+    struct in6_addr v6_rcv_saddr;
+    struct in6_addr v6_daddr;
+    u32             netns;
+};
+
+struct filter_rules {
+    u32 flags;
+    u32 prefixes;
+};
+
+struct filter_prefix {
+    u32 addr[4];
+    u32 mask[4];
+    u32 len;
+    u32 action;
+};
+
+#ifdef RINGBUF
+SEC("maps/events")
+struct bpf_map_def events = {
+    .type        = BPF_MAP_TYPE_RINGBUF,
+    .max_entries = 1 << 24,
+};
+
+SEC("maps/lost")
+struct bpf_map_def lost = {
+    .type        = BPF_MAP_TYPE_PERCPU_ARRAY,
+    .key_size    = sizeof(u32),
+    .value_size  = sizeof(u64),
+    .max_entries = 1,
+};
+
+static __always_inline int output(void *ctx, void *data, u64 size) {
+    int rc = bpf_ringbuf_output(&events, data, size, 0);
+    if (rc != 0) {
+        u32 key = 0;
+        u64 *count = bpf_map_lookup_elem(&lost, &key);
+        if (count != 0) {
+            *count += 1;
+        }
+    }
+    return rc;
+}
+#else
 SEC("maps/events")
 struct bpf_map_def events = {
     .type        = BPF_MAP_TYPE_PERF_EVENT_ARRAY,
@@ -63,73 +320,487 @@ struct bpf_map_def events = {
     .max_entries = 512,
 };
 
+#define output(ctx, data, size) bpf_perf_event_output(ctx, &events, BPF_F_CURRENT_CPU, data, size)
+#endif
+
 SEC("maps/socks")
 struct bpf_map_def socks = {
     .type        = BPF_MAP_TYPE_HASH,
     .key_size    = sizeof(u32),
-    .value_size  = sizeof(struct sock *),
+    .value_size  = sizeof(struct connect_call),
     .max_entries = 512,
 };
 
-SEC("kprobe/call-tcp-connect")
-int bpf_call_tcp_connect(struct pt_regs *ctx) {
+SEC("maps/live")
+struct bpf_map_def live = {
+    .type        = BPF_MAP_TYPE_HASH,
+    .key_size    = sizeof(struct sock *),
+    .value_size  = sizeof(struct live),
+    .max_entries = 10240,
+};
+
+SEC("maps/sends")
+struct bpf_map_def sends = {
+    .type        = BPF_MAP_TYPE_HASH,
+    .key_size    = sizeof(u32),
+    .value_size  = sizeof(struct msg_call),
+    .max_entries = 512,
+};
+
+SEC("maps/recvs")
+struct bpf_map_def recvs = {
+    .type        = BPF_MAP_TYPE_HASH,
+    .key_size    = sizeof(u32),
+    .value_size  = sizeof(struct msg_call),
+    .max_entries = 512,
+};
+
+SEC("maps/udp")
+struct bpf_map_def udp = {
+    .type        = BPF_MAP_TYPE_HASH,
+    .key_size    = sizeof(struct udp_key),
+    .value_size  = sizeof(struct udp_flow),
+    .max_entries = 10240,
+};
+
+SEC("maps/rules")
+struct bpf_map_def rules = {
+    .type        = BPF_MAP_TYPE_ARRAY,
+    .key_size    = sizeof(u32),
+    .value_size  = sizeof(struct filter_rules),
+    .max_entries = 1,
+};
+
+SEC("maps/ports")
+struct bpf_map_def ports = {
+    .type        = BPF_MAP_TYPE_HASH,
+    .key_size    = sizeof(u32),
+    .value_size  = sizeof(u32),
+    .max_entries = 1024,
+};
+
+SEC("maps/prefixes")
+struct bpf_map_def prefixes = {
+    .type        = BPF_MAP_TYPE_ARRAY,
+    .key_size    = sizeof(u32),
+    .value_size  = sizeof(struct filter_prefix),
+    .max_entries = PREFIXES,
+};
+
+SEC("maps/threads")
+struct bpf_map_def threads = {
+    .type        = BPF_MAP_TYPE_LRU_HASH,
+    .key_size    = sizeof(u32),
+    .value_size  = sizeof(u8),
+    .max_entries = 4096,
+};
+
+static __always_inline void read_sock4(struct sock4 *s, struct sock_info *sc, u32 proto) {
+    s->proto = proto;
+    s->saddr = sc->rcv_saddr;
+    s->sport = sc->num;
+    s->daddr = sc->daddr;
+    s->dport = ntohs(sc->dport);
+}
+
+static __always_inline void read_sock6(struct sock6 *s, struct sock_info *sc, u32 proto) {
+    s->proto = proto;
+    __builtin_memcpy(s->saddr, &sc->v6_rcv_saddr, sizeof(s->saddr));
+    s->sport = sc->num;
+    __builtin_memcpy(s->daddr, &sc->v6_daddr, sizeof(s->daddr));
+    s->dport = ntohs(sc->dport);
+}
+
+static __always_inline void map_sock4(struct sock6 *s, struct sock4 *sock4) {
+    s->proto = sock4->proto;
+    s->sport = sock4->sport;
+    s->dport = sock4->dport;
+
+    s->saddr[10] = 0xff;
+    s->saddr[11] = 0xff;
+    __builtin_memcpy(&s->saddr[12], &sock4->saddr, sizeof(sock4->saddr));
+
+    s->daddr[10] = 0xff;
+    s->daddr[11] = 0xff;
+    __builtin_memcpy(&s->daddr[12], &sock4->daddr, sizeof(sock4->daddr));
+}
+
+static __always_inline void read_sock(struct sock6 *s, struct sock_info *sc, u32 proto) {
+    if (sc->family == AF_INET6) {
+        read_sock6(s, sc, proto);
+    } else {
+        struct sock4 sock4;
+        read_sock4(&sock4, sc, proto);
+        map_sock4(s, &sock4);
+    }
+}
+
+static __always_inline void read_task(struct task *t) {
+    u64 pid_tgid = bpf_get_current_pid_tgid();
+    u64 uid_gid  = bpf_get_current_uid_gid();
+
+    t->tid = pid_tgid;
+    t->uid = uid_gid;
+    t->gid = uid_gid >> 32;
+    bpf_get_current_comm(&t->comm, sizeof(t->comm));
+}
+
+static __always_inline u32 sock_netns(struct sock *sk) {
+    struct net *net = NULL;
+    u32 inum = 0;
+
+    READ(net, sk->__sk_common.skc_net.net);
+    READ(inum, net->ns.inum);
+
+    return inum;
+}
+
+static __always_inline void read_sock_info(struct sock_info *s, struct sock *sk) {
+    READ(s->family,       sk->__sk_common.skc_family);
+    READ(s->state,        sk->__sk_common.skc_state);
+    READ(s->num,          sk->__sk_common.skc_num);
+    READ(s->dport,        sk->__sk_common.skc_dport);
+    READ(s->rcv_saddr,    sk->__sk_common.skc_rcv_saddr);
+    READ(s->daddr,        sk->__sk_common.skc_daddr);
+    READ(s->v6_rcv_saddr, sk->__sk_common.skc_v6_rcv_saddr);
+    READ(s->v6_daddr,     sk->__sk_common.skc_v6_daddr);
+    s->netns = sock_netns(sk);
+}
+
+static __always_inline u32 task_netns() {
+    struct task_struct *task = (struct task_struct *) bpf_get_current_task();
+    struct nsproxy *nsproxy = NULL;
+    struct net *net = NULL;
+    u32 inum = 0;
+
+    READ(nsproxy, task->nsproxy);
+    READ(net, nsproxy->net_ns);
+    READ(inum, net->ns.inum);
+
+    return inum;
+}
+
+static __always_inline int loopback(u8 *addr) {
+    u8 prefix = 0;
+
+#pragma unroll
+    for (int i = 0; i < 10; i++) {
+        prefix |= addr[i];
+    }
+
+    if (prefix != 0) {
+        return 0;
+    }
+
+    if (addr[10] == 0xff && addr[11] == 0xff) {
+        return addr[12] == 127;
+    }
+
+    return (addr[10] | addr[11] | addr[12] | addr[13] | addr[14]) == 0 && addr[15] == 1;
+}
+
+static __always_inline int filtered(struct sock6 *s) {
+    u32 key = 0;
+    struct filter_rules *filter = bpf_map_lookup_elem(&rules, &key);
+    if (filter == 0 || filter->flags == 0) {
+        return 0;
+    }
+
+    if (filter->flags & FILTER_LOOPBACK && loopback(s->daddr)) {
+        return 1;
+    }
+
+    if (filter->flags & (FILTER_ALLOW_PORTS | FILTER_DENY_PORTS)) {
+        u32 *sport = bpf_map_lookup_elem(&ports, &s->sport);
+        u32 *dport = bpf_map_lookup_elem(&ports, &s->dport);
+
+        u32 action = 0;
+        if (sport != 0) {
+            action |= *sport;
+        }
+        if (dport != 0) {
+            action |= *dport;
+        }
+
+        if (action & PORT_DENY) {
+            return 1;
+        }
+        if (filter->flags & FILTER_ALLOW_PORTS && !(action & PORT_ALLOW)) {
+            return 1;
+        }
+    }
+
+    if (filter->flags & (FILTER_INCLUDE | FILTER_EXCLUDE)) {
+        u32 addr[4];
+        __builtin_memcpy(addr, s->daddr, sizeof(addr));
+
+        u32 len = 0, action = 0;
+
+#pragma unroll
+        for (u32 i = 0; i < PREFIXES; i++) {
+            if (i >= filter->prefixes) {
+                break;
+            }
+
+            struct filter_prefix *prefix = bpf_map_lookup_elem(&prefixes, &i);
+            if (prefix == 0 || prefix->len < len) {
+                continue;
+            }
+
+            if ((addr[0] & prefix->mask[0]) == prefix->addr[0] &&
+                (addr[1] & prefix->mask[1]) == prefix->addr[1] &&
+                (addr[2] & prefix->mask[2]) == prefix->addr[2] &&
+                (addr[3] & prefix->mask[3]) == prefix->addr[3]) {
+                len    = prefix->len;
+                action = prefix->action;
+            }
+        }
+
+        if (action == PREFIX_EXCLUDE) {
+            return 1;
+        }
+        if (filter->flags & FILTER_INCLUDE && action != PREFIX_INCLUDE) {
+            return 1;
+        }
+    }
+
+    return 0;
+}
+
+static __always_inline int filtered4(struct sock4 *s) {
+    struct sock6 sock6 = {};
+    map_sock4(&sock6, s);
+    return filtered(&sock6);
+}
+
+static __always_inline int sock_filtered(struct sock_info *sc) {
+    struct sock6 sock6 = {};
+    read_sock(&sock6, sc, IPPROTO_TCP);
+    return filtered(&sock6);
+}
+
+static __always_inline void track_live(u32 pid, struct sock *sk, struct sock_info *sc, u64 start) {
+    struct live conn = {
+        .pid    = pid,
+        .cgroup = bpf_get_current_cgroup_id(),
+        .netns  = sc->netns,
+        .start  = start,
+    };
+    read_task(&conn.task);
+    read_sock(&conn.socket, sc, IPPROTO_TCP);
+
+    bpf_map_update_elem(&live, &sk, &conn, 0);
+}
+
+static __always_inline int update_live(struct pt_regs *ctx) {
     struct sock *sk = (void *) PT_REGS_PARM1(ctx);
 
+    struct live *conn = bpf_map_lookup_elem(&live, &sk);
+    if (conn == 0) {
+        return 0;
+    }
+
+    u64 rx = 0, tx = 0;
+    u32 srtt = 0, retx = 0;
+    struct tcp_sock *tcp = (struct tcp_sock *) sk;
+    READ(rx, tcp->bytes_received);
+    READ(tx, tcp->bytes_acked);
+    READ(srtt, tcp->srtt_us);
+    READ(retx, tcp->retrans_out);
+
+    conn->rx   = rx;
+    conn->tx   = tx;
+    conn->srtt = srtt >> 3;
+    conn->retx = retx;
+
+    return 0;
+}
+
+static __always_inline int output_connect_failed(struct pt_regs *ctx, struct header *owner, struct sock_info *sc, struct sockaddr *addr, int error) {
+    sa_family_t family = sc->family;
+    if (addr != NULL) {
+        bpf_probe_read(&family, sizeof(family), &addr->sa_family);
+    }
+
+    int rc;
+    if (family == AF_INET6) {
+        struct connect6_failed event = {
+            .header = {
+                .version = VERSION,
+                .size    = sizeof(struct header),
+                .length  = sizeof(event) - sizeof(struct header),
+                .kind    = CONNECT6_FAILED,
+                .pid     = owner->pid,
+                .ts      = bpf_ktime_get_ns(),
+                .cgroup  = owner->cgroup,
+                .netns   = sc->netns,
+                .task    = owner->task,
+            },
+            .error = error,
+        };
+        read_sock6(&event.socket, sc, IPPROTO_TCP);
+
+        if (addr != NULL) {
+            struct sockaddr_in6 sin6;
+            bpf_probe_read(&sin6, sizeof(sin6), addr);
+            __builtin_memcpy(event.socket.daddr, &sin6.sin6_addr, sizeof(event.socket.daddr));
+            event.socket.dport = ntohs(sin6.sin6_port);
+        }
+
+        if (filtered(&event.socket)) {
+            return 0;
+        }
+
+        rc = output(ctx, &event, sizeof(event));
+    } else {
+        struct connect_failed event = {
+            .header = {
+                .version = VERSION,
+                .size    = sizeof(struct header),
+                .length  = sizeof(event) - sizeof(struct header),
+                .kind    = CONNECT_FAILED,
+                .pid     = owner->pid,
+                .ts      = bpf_ktime_get_ns(),
+                .cgroup  = owner->cgroup,
+                .netns   = sc->netns,
+                .task    = owner->task,
+            },
+            .error = error,
+        };
+        read_sock4(&event.socket, sc, IPPROTO_TCP);
+
+        if (addr != NULL) {
+            struct sockaddr_in sin;
+            bpf_probe_read(&sin, sizeof(sin), addr);
+            event.socket.daddr = sin.sin_addr.s_addr;
+            event.socket.dport = ntohs(sin.sin_port);
+        }
+
+        if (filtered4(&event.socket)) {
+            return 0;
+        }
+
+        rc = output(ctx, &event, sizeof(event));
+    }
+
+    if (rc != 0) {
+        bpf_printk("connect failed event output failure: %d\n", rc);
+    }
+
+    return 0;
+}
+
+static __always_inline int call_tcp_connect(struct pt_regs *ctx) {
+    struct connect_call call = {
+        .sk    = (void *) PT_REGS_PARM1(ctx),
+        .addr  = (void *) PT_REGS_PARM2(ctx),
+        .start = bpf_ktime_get_ns(),
+    };
+
     u64 pid_tgid = bpf_get_current_pid_tgid();
-    u32 pid = pid_tgid >> 32;
     u32 tid = pid_tgid;
 
-    bpf_map_update_elem(&socks, &tid, &sk, 0);
+    bpf_map_update_elem(&socks, &tid, &call, 0);
 
     return 0;
 }
 
-SEC("kretprobe/exit-tcp-connect")
-int bpf_exit_tcp_connect(struct pt_regs *ctx) {
+static __always_inline int exit_tcp_connect(struct pt_regs *ctx) {
     int rc = PT_REGS_RC(ctx);
 
     u64 pid_tgid = bpf_get_current_pid_tgid();
     u32 pid = pid_tgid >> 32;
     u32 tid = pid_tgid;
 
-    struct sock **skp = bpf_map_lookup_elem(&socks, &tid);
-    if (skp == 0) {
+    struct connect_call *call = bpf_map_lookup_elem(&socks, &tid);
+    if (call == 0) {
         return 0;
     }
 
+    struct sock *sk = call->sk;
+    struct sock_info sc = {};
+    read_sock_info(&sc, sk);
+
     if (rc != 0) {
+        struct header owner = {
+            .pid    = pid,
+            .cgroup = bpf_get_current_cgroup_id(),
+        };
+        read_task(&owner.task);
+
+        output_connect_failed(ctx, &owner, &sc, call->addr, rc);
         bpf_map_delete_elem(&socks, &tid);
         return 0;
     }
 
-    struct sock *sk = *skp;
-    struct sock_common sc;
-    bpf_probe_read(&sc, sizeof(sc), &sk->__sk_common);
+    if (sock_filtered(&sc)) {
+        bpf_map_delete_elem(&socks, &tid);
+        return 0;
+    }
 
-    struct connect event = {
-        .header = {
-            .kind = CONNECT,
-            .pid  = pid,
-        },
-        .socket = {
-            .proto = 6,
-            .saddr = sc.skc_rcv_saddr,
-            .sport = sc.skc_num,
-            .daddr = sc.skc_daddr,
-            .dport = ntohs(sc.skc_dport),
-        },
-    };
+    if (sc.family == AF_INET6) {
+        struct connect6 event = {
+            .header = {
+                .version = VERSION,
+                .size    = sizeof(struct header),
+                .length  = sizeof(event) - sizeof(struct header),
+                .kind    = CONNECT6,
+                .pid     = pid,
+                .ts      = bpf_ktime_get_ns(),
+                .cgroup  = bpf_get_current_cgroup_id(),
+                .netns   = sc.netns,
+            },
+        };
+        read_task(&event.header.task);
+        read_sock6(&event.socket, &sc, IPPROTO_TCP);
+        rc = output(ctx, &event, sizeof(event));
+    } else {
+        struct connect event = {
+            .header = {
+                .version = VERSION,
+                .size    = sizeof(struct header),
+                .length  = sizeof(event) - sizeof(struct header),
+                .kind    = CONNECT,
+                .pid     = pid,
+                .ts      = bpf_ktime_get_ns(),
+                .cgroup  = bpf_get_current_cgroup_id(),
+                .netns   = sc.netns,
+            },
+        };
+        read_task(&event.header.task);
+        read_sock4(&event.socket, &sc, IPPROTO_TCP);
+        rc = output(ctx, &event, sizeof(event));
+    }
 
-    rc = bpf_perf_event_output(ctx, &events, BPF_F_CURRENT_CPU, &event, sizeof(event));
     if (rc != 0) {
         bpf_printk("connect event output failure: %d\n", rc);
     }
+    track_live(pid, sk, &sc, call->start);
     bpf_map_delete_elem(&socks, &tid);
 
-
     return 0;
 }
 
+SEC("kprobe/call-tcp-connect")
+int bpf_call_tcp_connect(struct pt_regs *ctx) {
+    return call_tcp_connect(ctx);
+}
+
+SEC("kretprobe/exit-tcp-connect")
+int bpf_exit_tcp_connect(struct pt_regs *ctx) {
+    return exit_tcp_connect(ctx);
+}
+
+SEC("kprobe/call-tcp-v6-connect")
+int bpf_call_tcp_v6_connect(struct pt_regs *ctx) {
+    return call_tcp_connect(ctx);
+}
+
+SEC("kretprobe/exit-tcp-v6-connect")
+int bpf_exit_tcp_v6_connect(struct pt_regs *ctx) {
+    return exit_tcp_connect(ctx);
+}
+
 SEC("kretprobe/inet_csk_accept")
 int bpf_call_inet_csk_accept(struct pt_regs *ctx) {
     struct sock *sk = (void *) PT_REGS_RC(ctx);
@@ -140,29 +811,53 @@ int bpf_call_inet_csk_accept(struct pt_regs *ctx) {
 
     u64 pid_tgid = bpf_get_current_pid_tgid();
     u32 pid = pid_tgid >> 32;
-    u32 tid = pid_tgid;
 
-    struct sock_common sc;
-    bpf_probe_read(&sc, sizeof(sc), &sk->__sk_common);
+    struct sock_info sc = {};
+    read_sock_info(&sc, sk);
 
-    struct accept event = {
-        .header = {
-            .kind = ACCEPT,
-            .pid  = pid,
-        },
-        .socket = {
-            .proto = 6,
-            .saddr = sc.skc_rcv_saddr,
-            .sport = sc.skc_num,
-            .daddr = sc.skc_daddr,
-            .dport = ntohs(sc.skc_dport),
-        },
-    };
+    if (sock_filtered(&sc)) {
+        return 0;
+    }
+
+    int rc;
+    if (sc.family == AF_INET6) {
+        struct accept6 event = {
+            .header = {
+                .version = VERSION,
+                .size    = sizeof(struct header),
+                .length  = sizeof(event) - sizeof(struct header),
+                .kind    = ACCEPT6,
+                .pid     = pid,
+                .ts      = bpf_ktime_get_ns(),
+                .cgroup  = bpf_get_current_cgroup_id(),
+                .netns   = sc.netns,
+            },
+        };
+        read_task(&event.header.task);
+        read_sock6(&event.socket, &sc, IPPROTO_TCP);
+        rc = output(ctx, &event, sizeof(event));
+    } else {
+        struct accept event = {
+            .header = {
+                .version = VERSION,
+                .size    = sizeof(struct header),
+                .length  = sizeof(event) - sizeof(struct header),
+                .kind    = ACCEPT,
+                .pid     = pid,
+                .ts      = bpf_ktime_get_ns(),
+                .cgroup  = bpf_get_current_cgroup_id(),
+                .netns   = sc.netns,
+            },
+        };
+        read_task(&event.header.task);
+        read_sock4(&event.socket, &sc, IPPROTO_TCP);
+        rc = output(ctx, &event, sizeof(event));
+    }
 
-    int rc = bpf_perf_event_output(ctx, &events, BPF_F_CURRENT_CPU, &event, sizeof(event));
     if (rc != 0) {
         bpf_printk("accept event output failure: %d\n", rc);
     }
+    track_live(pid, sk, &sc, 0);
 
     return 0;
 }
@@ -175,58 +870,298 @@ int bpf_call_tcp_close(struct pt_regs *ctx) {
     u32 pid = pid_tgid >> 32;
     u32 tid = pid_tgid;
 
-    struct sock_common sc;
-    bpf_probe_read(&sc, sizeof(sc), &sk->__sk_common);
+    struct sock_info sc = {};
+    read_sock_info(&sc, sk);
 
-    u32 rx = 0, tx = 0, srtt = 0, retx = 0;
+    if (sock_filtered(&sc)) {
+        bpf_map_delete_elem(&socks, &tid);
+        bpf_map_delete_elem(&live, &sk);
+        return 0;
+    }
+
+    u64 rx = 0, tx = 0;
+    u32 srtt = 0, retx = 0;
     struct tcp_sock *tcp = (struct tcp_sock *) sk;
-    bpf_probe_read(&rx, sizeof(rx), __builtin_preserve_access_index(&tcp->bytes_received));
-    bpf_probe_read(&tx, sizeof(tx), __builtin_preserve_access_index(&tcp->bytes_acked));
-    bpf_probe_read(&srtt, sizeof(srtt), __builtin_preserve_access_index(&tcp->srtt_us));
-    bpf_probe_read(&retx, sizeof(retx), __builtin_preserve_access_index(&tcp->retrans_out));
+    READ(rx, tcp->bytes_received);
+    READ(tx, tcp->bytes_acked);
+    READ(srtt, tcp->srtt_us);
+    READ(retx, tcp->retrans_out);
 
-    struct close event = {
-        .header = {
-            .kind = CLOSE,
-            .pid  = pid,
-        },
-        .socket = {
-            .proto = 6,
-            .saddr = sc.skc_rcv_saddr,
-            .sport = sc.skc_num,
-            .daddr = sc.skc_daddr,
-            .dport = ntohs(sc.skc_dport),
-        },
-        .rx   = rx,
-        .tx   = tx,
-        .srtt = srtt >> 3,
-        .retx = retx,
-    };
+    int rc;
+    if (sc.family == AF_INET6) {
+        struct close6 event = {
+            .header = {
+                .version = VERSION,
+                .size    = sizeof(struct header),
+                .length  = sizeof(event) - sizeof(struct header),
+                .kind    = CLOSE6,
+                .pid     = pid,
+                .ts      = bpf_ktime_get_ns(),
+                .cgroup  = bpf_get_current_cgroup_id(),
+                .netns   = sc.netns,
+            },
+            .rx   = rx,
+            .tx   = tx,
+            .srtt = srtt >> 3,
+            .retx = retx,
+        };
+        read_task(&event.header.task);
+        read_sock6(&event.socket, &sc, IPPROTO_TCP);
+        rc = output(ctx, &event, sizeof(event));
+    } else {
+        struct close event = {
+            .header = {
+                .version = VERSION,
+                .size    = sizeof(struct header),
+                .length  = sizeof(event) - sizeof(struct header),
+                .kind    = CLOSE,
+                .pid     = pid,
+                .ts      = bpf_ktime_get_ns(),
+                .cgroup  = bpf_get_current_cgroup_id(),
+                .netns   = sc.netns,
+            },
+            .rx   = rx,
+            .tx   = tx,
+            .srtt = srtt >> 3,
+            .retx = retx,
+        };
+        read_task(&event.header.task);
+        read_sock4(&event.socket, &sc, IPPROTO_TCP);
+        rc = output(ctx, &event, sizeof(event));
+    }
 
-    int rc = bpf_perf_event_output(ctx, &events, BPF_F_CURRENT_CPU, &event, sizeof(event));
     if (rc != 0) {
         bpf_printk("close event output failure: %d\n", rc);
     }
     bpf_map_delete_elem(&socks, &tid);
+    bpf_map_delete_elem(&live, &sk);
+
+    return 0;
+}
+
+SEC("kprobe/tcp_done")
+int bpf_call_tcp_done(struct pt_regs *ctx) {
+    struct sock *sk = (void *) PT_REGS_PARM1(ctx);
+
+    struct live *conn = bpf_map_lookup_elem(&live, &sk);
+    if (conn == 0) {
+        return 0;
+    }
+    struct header owner = {
+        .pid    = conn->pid,
+        .cgroup = conn->cgroup,
+        .task   = conn->task,
+    };
+
+    struct sock_info sc = {};
+    read_sock_info(&sc, sk);
+
+    int err = 0;
+    READ(err, sk->sk_err);
+
+    if (sc.state == TCP_SYN_SENT && err != 0) {
+        output_connect_failed(ctx, &owner, &sc, NULL, -err);
+    }
+    bpf_map_delete_elem(&live, &sk);
 
     return 0;
 }
 
+SEC("kprobe/tcp_sendmsg")
+int bpf_call_tcp_sendmsg(struct pt_regs *ctx) {
+    return update_live(ctx);
+}
+
+SEC("kprobe/tcp_cleanup_rbuf")
+int bpf_call_tcp_cleanup_rbuf(struct pt_regs *ctx) {
+    return update_live(ctx);
+}
+
+static __always_inline void track_udp(struct sock *sk, struct msghdr *msg, u32 rx, u32 tx) {
+    u64 pid_tgid = bpf_get_current_pid_tgid();
+
+    struct sock_info sc = {};
+    read_sock_info(&sc, sk);
+
+    struct udp_key key = {
+        .pid = pid_tgid >> 32,
+    };
+    read_sock(&key.socket, &sc, IPPROTO_UDP);
+
+    void *name = NULL;
+    READ(name, msg->msg_name);
+
+    if (name != NULL) {
+        sa_family_t family = 0;
+        bpf_probe_read(&family, sizeof(family), name);
+
+        if (family == AF_INET6) {
+            struct sockaddr_in6 sin6;
+            bpf_probe_read(&sin6, sizeof(sin6), name);
+            __builtin_memcpy(key.socket.daddr, &sin6.sin6_addr, sizeof(key.socket.daddr));
+            key.socket.dport = ntohs(sin6.sin6_port);
+        } else if (family == AF_INET) {
+            struct sockaddr_in sin;
+            bpf_probe_read(&sin, sizeof(sin), name);
+            __builtin_memset(key.socket.daddr, 0, sizeof(key.socket.daddr));
+            key.socket.daddr[10] = 0xff;
+            key.socket.daddr[11] = 0xff;
+            __builtin_memcpy(&key.socket.daddr[12], &sin.sin_addr.s_addr, sizeof(sin.sin_addr.s_addr));
+            key.socket.dport = ntohs(sin.sin_port);
+        }
+    }
+
+    if (filtered(&key.socket)) {
+        return;
+    }
+
+    struct udp_flow *flow = bpf_map_lookup_elem(&udp, &key);
+    if (flow == 0) {
+        struct udp_flow init = {
+            .cgroup = bpf_get_current_cgroup_id(),
+            .netns  = sc.netns,
+        };
+        read_task(&init.task);
+
+        bpf_map_update_elem(&udp, &key, &init, BPF_NOEXIST);
+
+        flow = bpf_map_lookup_elem(&udp, &key);
+        if (flow == 0) {
+            return;
+        }
+    }
+
+    flow->ts = bpf_ktime_get_ns();
+    __sync_fetch_and_add(&flow->rx, rx);
+    __sync_fetch_and_add(&flow->tx, tx);
+    __sync_fetch_and_add(&flow->rx_pkts, rx > 0);
+    __sync_fetch_and_add(&flow->tx_pkts, tx > 0);
+}
+
+static __always_inline int call_udp_msg(struct pt_regs *ctx, struct bpf_map_def *calls) {
+    struct msg_call call = {
+        .sk  = (void *) PT_REGS_PARM1(ctx),
+        .msg = (void *) PT_REGS_PARM2(ctx),
+    };
+
+    u64 pid_tgid = bpf_get_current_pid_tgid();
+    u32 tid = pid_tgid;
+
+    bpf_map_update_elem(calls, &tid, &call, 0);
+
+    return 0;
+}
+
+static __always_inline int exit_udp_msg(struct pt_regs *ctx, struct bpf_map_def *calls, int send) {
+    int rc = PT_REGS_RC(ctx);
+
+    u64 pid_tgid = bpf_get_current_pid_tgid();
+    u32 tid = pid_tgid;
+
+    struct msg_call *call = bpf_map_lookup_elem(calls, &tid);
+    if (call == 0) {
+        return 0;
+    }
+
+    if (rc > 0) {
+        track_udp(call->sk, call->msg, send ? 0 : rc, send ? rc : 0);
+    }
+    bpf_map_delete_elem(calls, &tid);
+
+    return 0;
+}
+
+SEC("kprobe/call-udp-sendmsg")
+int bpf_call_udp_sendmsg(struct pt_regs *ctx) {
+    return call_udp_msg(ctx, &sends);
+}
+
+SEC("kretprobe/exit-udp-sendmsg")
+int bpf_exit_udp_sendmsg(struct pt_regs *ctx) {
+    return exit_udp_msg(ctx, &sends, 1);
+}
+
+SEC("kprobe/call-udpv6-sendmsg")
+int bpf_call_udpv6_sendmsg(struct pt_regs *ctx) {
+    return call_udp_msg(ctx, &sends);
+}
+
+SEC("kretprobe/exit-udpv6-sendmsg")
+int bpf_exit_udpv6_sendmsg(struct pt_regs *ctx) {
+    return exit_udp_msg(ctx, &sends, 1);
+}
+
+SEC("kprobe/call-udp-recvmsg")
+int bpf_call_udp_recvmsg(struct pt_regs *ctx) {
+    return call_udp_msg(ctx, &recvs);
+}
+
+SEC("kretprobe/exit-udp-recvmsg")
+int bpf_exit_udp_recvmsg(struct pt_regs *ctx) {
+    return exit_udp_msg(ctx, &recvs, 0);
+}
+
+SEC("kprobe/call-udpv6-recvmsg")
+int bpf_call_udpv6_recvmsg(struct pt_regs *ctx) {
+    return call_udp_msg(ctx, &recvs);
+}
+
+SEC("kretprobe/exit-udpv6-recvmsg")
+int bpf_exit_udpv6_recvmsg(struct pt_regs *ctx) {
+    return exit_udp_msg(ctx, &recvs, 0);
+}
+
 typedef struct {
     u64   __pad;
-    char  *filename;
+    u32   filename;
     pid_t pid;
     pid_t old_pid;
 } sched_process_exec_ctx;
 
+static __always_inline void read_exec(struct exec *e, sched_process_exec_ctx *ctx) {
+    struct task_struct *task = (struct task_struct *) bpf_get_current_task();
+    struct task_struct *parent;
+    struct mm_struct *mm;
+    unsigned long start, end;
+
+    READ(parent, task->real_parent);
+    READ(e->ppid, parent->tgid);
+
+    bpf_probe_read_str(&e->filename, sizeof(e->filename), (void *) ctx + (ctx->filename & 0xFFFF));
+
+    READ(mm, task->mm);
+    READ(start, mm->arg_start);
+    READ(end, mm->arg_end);
+
+    u32 len = end > start ? end - start : 0;
+    if (len > ARGV_SIZE) {
+        len = ARGV_SIZE;
+    }
+
+    if (len > 0) {
+        bpf_probe_read(&e->argv, len, (void *) start);
+        e->argv_len = len;
+    }
+}
+
 SEC("tracepoint/sched/sched_process_exec")
 int bpf_trace_sched_process_exec(sched_process_exec_ctx *ctx) {
-    struct header event = {
-        .kind = EXEC,
-        .pid  = ctx->pid,
+    struct exec event = {
+        .header = {
+            .version = VERSION,
+            .size    = sizeof(struct header),
+            .length  = sizeof(event) - sizeof(struct header),
+            .kind    = EXEC,
+            .pid     = ctx->pid,
+            .ts      = bpf_ktime_get_ns(),
+            .cgroup  = bpf_get_current_cgroup_id(),
+            .netns   = task_netns(),
+        },
     };
+    read_task(&event.header.task);
+    read_exec(&event, ctx);
 
-    int rc = bpf_perf_event_output(ctx, &events, BPF_F_CURRENT_CPU, &event, sizeof(event));
+    int rc = output(ctx, &event, sizeof(event));
     if (rc != 0) {
         bpf_printk("exec event output failure: %d\n", rc);
     }
@@ -234,6 +1169,65 @@ int bpf_trace_sched_process_exec(sched_process_exec_ctx *ctx) {
     return 0;
 }
 
+typedef struct {
+    u64           __pad;
+    pid_t         pid;
+    char          comm[16];
+    unsigned long clone_flags;
+    short         oom_score_adj;
+} task_newtask_ctx;
+
+SEC("tracepoint/task/task_newtask")
+int bpf_trace_task_newtask(task_newtask_ctx *ctx) {
+    if (ctx->clone_flags & CLONE_THREAD) {
+        u32 pid   = ctx->pid;
+        u8 thread = 1;
+        bpf_map_update_elem(&threads, &pid, &thread, BPF_ANY);
+    }
+
+    return 0;
+}
+
+typedef struct {
+    u64   __pad;
+    char  parent_comm[16];
+    pid_t parent_pid;
+    char  child_comm[16];
+    pid_t child_pid;
+} sched_process_fork_ctx;
+
+SEC("tracepoint/sched/sched_process_fork")
+int bpf_trace_sched_process_fork(sched_process_fork_ctx *ctx) {
+    u32 pid = ctx->child_pid;
+
+    if (bpf_map_lookup_elem(&threads, &pid)) {
+        bpf_map_delete_elem(&threads, &pid);
+        return 0;
+    }
+
+    struct fork event = {
+        .header = {
+            .version = VERSION,
+            .size    = sizeof(struct header),
+            .length  = sizeof(event) - sizeof(struct header),
+            .kind    = FORK,
+            .pid     = ctx->child_pid,
+            .ts      = bpf_ktime_get_ns(),
+            .cgroup  = bpf_get_current_cgroup_id(),
+            .netns   = task_netns(),
+        },
+        .ppid = bpf_get_current_pid_tgid() >> 32,
+    };
+    read_task(&event.header.task);
+
+    int rc = output(ctx, &event, sizeof(event));
+    if (rc != 0) {
+        bpf_printk("fork event output failure: %d\n", rc);
+    }
+
+    return 0;
+}
+
 typedef struct {
     u64   __pad;
     char  comm[16];
@@ -244,11 +1238,18 @@ typedef struct {
 SEC("tracepoint/sched/sched_process_exit")
 int bpf_trace_sched_process_exit(sched_process_exit_ctx *ctx) {
     struct header event = {
-        .kind = EXIT,
-        .pid  = ctx->pid,
+        .version = VERSION,
+        .size    = sizeof(struct header),
+        .length  = sizeof(event) - sizeof(struct header),
+        .kind    = EXIT,
+        .pid     = ctx->pid,
+        .ts      = bpf_ktime_get_ns(),
+        .cgroup  = bpf_get_current_cgroup_id(),
+        .netns   = task_netns(),
     };
+    read_task(&event.task);
 
-    int rc = bpf_perf_event_output(ctx, &events, BPF_F_CURRENT_CPU, &event, sizeof(event));
+    int rc = output(ctx, &event, sizeof(event));
     if (rc != 0) {
         bpf_printk("exit event output failure: %d\n", rc);
     }
@@ -256,5 +1257,240 @@ int bpf_trace_sched_process_exit(sched_process_exit_ctx *ctx) {
     return 0;
 }
 
+typedef struct {
+    u64         __pad;
+    const void *skaddr;
+    int         oldstate;
+    int         newstate;
+    u16         sport;
+    u16         dport;
+    u16         family;
+    u16         protocol;
+    u8          saddr[4];
+    u8          daddr[4];
+    u8          saddr_v6[16];
+    u8          daddr_v6[16];
+} inet_sock_set_state_ctx;
+
+static __always_inline int process_context(int state) {
+    switch (state) {
+        case TCP_SYN_SENT:
+        case TCP_FIN_WAIT1:
+        case TCP_LAST_ACK:
+        case TCP_LISTEN:
+            return 1;
+        default:
+            return 0;
+    }
+}
+
+SEC("tracepoint/sock/inet_sock_set_state")
+int bpf_trace_inet_sock_set_state(inet_sock_set_state_ctx *ctx) {
+    if (ctx->protocol != IPPROTO_TCP) {
+        return 0;
+    }
+
+    struct sock *sk = (void *) ctx->skaddr;
+    struct header owner = {};
+    u64 start = 0;
+
+    u32 tid = bpf_get_current_pid_tgid();
+    struct connect_call *call = bpf_map_lookup_elem(&socks, &tid);
+    if (call != 0 && call->sk == sk) {
+        start = call->start;
+    }
+
+    struct live *conn = bpf_map_lookup_elem(&live, &sk);
+    if (conn != 0) {
+        owner.pid    = conn->pid;
+        owner.cgroup = conn->cgroup;
+        owner.task   = conn->task;
+        start        = conn->start;
+    } else if (process_context(ctx->newstate)) {
+        owner.pid    = bpf_get_current_pid_tgid() >> 32;
+        owner.cgroup = bpf_get_current_cgroup_id();
+        read_task(&owner.task);
+    }
+
+    int err = 0;
+    if (ctx->newstate == TCP_CLOSE) {
+        READ(err, sk->sk_err);
+    }
+
+    int rc;
+    if (ctx->family == AF_INET6) {
+        struct state6 event = {
+            .header = {
+                .version = VERSION,
+                .size    = sizeof(struct header),
+                .length  = sizeof(event) - sizeof(struct header),
+                .kind    = STATE6,
+                .pid     = owner.pid,
+                .ts      = bpf_ktime_get_ns(),
+                .cgroup  = owner.cgroup,
+                .netns   = sock_netns(sk),
+                .task    = owner.task,
+            },
+            .socket = {
+                .proto = IPPROTO_TCP,
+                .sport = ctx->sport,
+                .dport = ctx->dport,
+            },
+            .sk    = (u64) sk,
+            .start = start,
+            .old   = ctx->oldstate,
+            .new   = ctx->newstate,
+            .error = err,
+        };
+        __builtin_memcpy(event.socket.saddr, ctx->saddr_v6, sizeof(event.socket.saddr));
+        __builtin_memcpy(event.socket.daddr, ctx->daddr_v6, sizeof(event.socket.daddr));
+
+        if (filtered(&event.socket)) {
+            return 0;
+        }
+
+        rc = output(ctx, &event, sizeof(event));
+    } else {
+        struct state event = {
+            .header = {
+                .version = VERSION,
+                .size    = sizeof(struct header),
+                .length  = sizeof(event) - sizeof(struct header),
+                .kind    = STATE,
+                .pid     = owner.pid,
+                .ts      = bpf_ktime_get_ns(),
+                .cgroup  = owner.cgroup,
+                .netns   = sock_netns(sk),
+                .task    = owner.task,
+            },
+            .socket = {
+                .proto = IPPROTO_TCP,
+                .sport = ctx->sport,
+                .dport = ctx->dport,
+            },
+            .sk    = (u64) sk,
+            .start = start,
+            .old   = ctx->oldstate,
+            .new   = ctx->newstate,
+            .error = err,
+        };
+        __builtin_memcpy(&event.socket.saddr, ctx->saddr, sizeof(event.socket.saddr));
+        __builtin_memcpy(&event.socket.daddr, ctx->daddr, sizeof(event.socket.daddr));
+
+        if (filtered4(&event.socket)) {
+            return 0;
+        }
+
+        rc = output(ctx, &event, sizeof(event));
+    }
+
+    if (rc != 0) {
+        bpf_printk("state event output failure: %d\n", rc);
+    }
+
+    return 0;
+}
+
+static __always_inline int output_tcp_event(void *ctx, struct sock *sk, u32 kind, u32 kind6) {
+    if (sk == NULL) {
+        return 0;
+    }
+
+    struct header owner = {};
+
+    struct live *conn = bpf_map_lookup_elem(&live, &sk);
+    if (conn != 0) {
+        owner.pid    = conn->pid;
+        owner.cgroup = conn->cgroup;
+        owner.task   = conn->task;
+    }
+
+    struct sock_info sc = {};
+    read_sock_info(&sc, sk);
+
+    if (sock_filtered(&sc)) {
+        return 0;
+    }
+
+    int rc;
+    if (sc.family == AF_INET6) {
+        struct tcp_event6 event = {
+            .header = {
+                .version = VERSION,
+                .size    = sizeof(struct header),
+                .length  = sizeof(event) - sizeof(struct header),
+                .kind    = kind6,
+                .pid     = owner.pid,
+                .ts      = bpf_ktime_get_ns(),
+                .cgroup  = owner.cgroup,
+                .netns   = sc.netns,
+                .task    = owner.task,
+            },
+        };
+        read_sock6(&event.socket, &sc, IPPROTO_TCP);
+        rc = output(ctx, &event, sizeof(event));
+    } else {
+        struct tcp_event event = {
+            .header = {
+                .version = VERSION,
+                .size    = sizeof(struct header),
+                .length  = sizeof(event) - sizeof(struct header),
+                .kind    = kind,
+                .pid     = owner.pid,
+                .ts      = bpf_ktime_get_ns(),
+                .cgroup  = owner.cgroup,
+                .netns   = sc.netns,
+                .task    = owner.task,
+            },
+        };
+        read_sock4(&event.socket, &sc, IPPROTO_TCP);
+        rc = output(ctx, &event, sizeof(event));
+    }
+
+    if (rc != 0) {
+        bpf_printk("tcp event output failure: %d\n", rc);
+    }
+
+    return 0;
+}
+
+typedef struct {
+    u64         __pad;
+    const void *skbaddr;
+    const void *skaddr;
+} tcp_event_sk_skb_ctx;
+
+typedef struct {
+    u64         __pad;
+    const void *skaddr;
+} tcp_event_sk_ctx;
+
+SEC("tracepoint/tcp/tcp_retransmit_skb")
+int bpf_trace_tcp_retransmit_skb(tcp_event_sk_skb_ctx *ctx) {
+    return output_tcp_event(ctx, (void *) ctx->skaddr, RETRANSMIT, RETRANSMIT6);
+}
+
+SEC("tracepoint/tcp/tcp_send_reset")
+int bpf_trace_tcp_send_reset(tcp_event_sk_skb_ctx *ctx) {
+    return output_tcp_event(ctx, (void *) ctx->skaddr, SEND_RESET, SEND_RESET6);
+}
+
+SEC("tracepoint/tcp/tcp_receive_reset")
+int bpf_trace_tcp_receive_reset(tcp_event_sk_ctx *ctx) {
+    return output_tcp_event(ctx, (void *) ctx->skaddr, RECEIVE_RESET, RECEIVE_RESET6);
+}
+
+struct meta {
+    u32 version;
+    u32 kinds;
+};
+
+struct meta _meta SEC("meta") = {
+    .version = VERSION,
+    .kinds   = FORK + 1,
+};
+
 char  _license[] SEC("license") = "GPL";
+#ifndef CORE
 __u32 _version   SEC("version") = LINUX_VERSION_CODE;
+#endif
@@ -0,0 +1,43 @@
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    let arch = match env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default().as_str() {
+        "aarch64" => "arm64",
+        "x86_64"  => "x86",
+        _         => return,
+    };
+
+    let core    = env::var_os("CARGO_FEATURE_CORE").is_some();
+    let ringbuf = env::var_os("CARGO_FEATURE_RINGBUF").is_some();
+
+    let objects = [
+        (true,            "bytecode"),
+        (ringbuf,         "bytecode.ringbuf"),
+        (core,            "bytecode.core"),
+        (core && ringbuf, "bytecode.core.ringbuf"),
+    ];
+
+    println!("cargo:rerun-if-changed=bpf/bytecode.c");
+    println!("cargo:rerun-if-changed=bpf/Makefile");
+
+    for (_, name) in objects.iter().filter(|(enabled, _)| *enabled) {
+        let object = format!("{}.{}.o", name, arch);
+
+        println!("cargo:rerun-if-changed=bpf/{}", object);
+
+        if Path::new("bpf").join(&object).exists() {
+            continue;
+        }
+
+        let status = Command::new("make")
+            .args(&["-C", "bpf", &object, &format!("ARCH={}", arch)])
+            .status()
+            .unwrap_or_else(|e| panic!("failed to run make for bpf/{}: {}", object, e));
+
+        if !status.success() {
+            panic!("failed to build bpf/{}, see bpf/Makefile for requirements", object);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use anyhow::{anyhow, Result};
+
+pub struct Target {
+    pub program:  &'static str,
+    pub hook:     Hook,
+    pub required: bool,
+}
+
+pub enum Hook {
+    KProbe(&'static [&'static str]),
+    TracePoint(&'static str, &'static str),
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub attached: Vec<Attached>,
+    pub skipped:  Vec<Skipped>,
+}
+
+#[derive(Debug)]
+pub struct Attached {
+    pub program: String,
+    pub target:  String,
+}
+
+#[derive(Debug)]
+pub struct Skipped {
+    pub program:  String,
+    pub reason:   String,
+    pub required: bool,
+}
+
+pub struct Kernel {
+    symbols: Option<HashSet<String>>,
+    tracefs: Option<&'static Path>,
+}
+
+pub const TARGETS: &[Target] = &[
+    Target { program: "call-tcp-connect",         hook: Hook::KProbe(&["tcp_v4_connect"]),                    required: true  },
+    Target { program: "exit-tcp-connect",         hook: Hook::KProbe(&["tcp_v4_connect"]),                    required: true  },
+    Target { program: "call-tcp-v6-connect",      hook: Hook::KProbe(&["tcp_v6_connect"]),                    required: false },
+    Target { program: "exit-tcp-v6-connect",      hook: Hook::KProbe(&["tcp_v6_connect"]),                    required: false },
+    Target { program: "inet_csk_accept",          hook: Hook::KProbe(&["inet_csk_accept"]),                   required: true  },
+    Target { program: "tcp_close",                hook: Hook::KProbe(&["tcp_close"]),                         required: true  },
+    Target { program: "tcp_done",                 hook: Hook::KProbe(&["tcp_done"]),                          required: false },
+    Target { program: "tcp_sendmsg",              hook: Hook::KProbe(&["tcp_sendmsg", "tcp_sendmsg_locked"]), required: false },
+    Target { program: "tcp_cleanup_rbuf",         hook: Hook::KProbe(&["tcp_cleanup_rbuf", "tcp_recvmsg"]),   required: false },
+    Target { program: "call-udp-sendmsg",         hook: Hook::KProbe(&["udp_sendmsg"]),                       required: false },
+    Target { program: "exit-udp-sendmsg",         hook: Hook::KProbe(&["udp_sendmsg"]),                       required: false },
+    Target { program: "call-udpv6-sendmsg",       hook: Hook::KProbe(&["udpv6_sendmsg"]),                     required: false },
+    Target { program: "exit-udpv6-sendmsg",       hook: Hook::KProbe(&["udpv6_sendmsg"]),                     required: false },
+    Target { program: "call-udp-recvmsg",         hook: Hook::KProbe(&["udp_recvmsg"]),                       required: false },
+    Target { program: "exit-udp-recvmsg",         hook: Hook::KProbe(&["udp_recvmsg"]),                       required: false },
+    Target { program: "call-udpv6-recvmsg",       hook: Hook::KProbe(&["udpv6_recvmsg"]),                     required: false },
+    Target { program: "exit-udpv6-recvmsg",       hook: Hook::KProbe(&["udpv6_recvmsg"]),                     required: false },
+    Target { program: "sched/sched_process_exec", hook: Hook::TracePoint("sched", "sched_process_exec"),      required: true  },
+    Target { program: "sched/sched_process_fork", hook: Hook::TracePoint("sched", "sched_process_fork"),      required: false },
+    Target { program: "task/task_newtask",        hook: Hook::TracePoint("task", "task_newtask"),             required: false },
+    Target { program: "sched/sched_process_exit", hook: Hook::TracePoint("sched", "sched_process_exit"),      required: true  },
+    Target { program: "sock/inet_sock_set_state", hook: Hook::TracePoint("sock", "inet_sock_set_state"),      required: false },
+    Target { program: "tcp/tcp_retransmit_skb",   hook: Hook::TracePoint("tcp", "tcp_retransmit_skb"),        required: false },
+    Target { program: "tcp/tcp_send_reset",       hook: Hook::TracePoint("tcp", "tcp_send_reset"),            required: false },
+    Target { program: "tcp/tcp_receive_reset",    hook: Hook::TracePoint("tcp", "tcp_receive_reset"),         required: false },
+];
+
+impl Kernel {
+    pub fn probe() -> Self {
+        let symbols = fs::read_to_string("/proc/kallsyms").ok().map(|kallsyms| {
+            kallsyms.lines().filter_map(|line| {
+                match line.split_whitespace().collect::<Vec<_>>()[..] {
+                    [_, "t", name, ..] | [_, "T", name, ..] => Some(name.to_owned()),
+                    _                                       => None,
+                }
+            }).collect::<HashSet<_>>()
+        }).filter(|symbols| !symbols.is_empty());
+
+        let tracefs = TRACEFS.iter().map(Path::new).find(|path| {
+            path.join("events").exists()
+        });
+
+        Self { symbols, tracefs }
+    }
+
+    pub fn symbols(&self, candidates: &[&str]) -> Vec<String> {
+        let symbols = match &self.symbols {
+            Some(symbols) => symbols,
+            None          => return candidates.iter().map(|name| name.to_string()).collect(),
+        };
+
+        candidates.iter().filter_map(|name| {
+            if symbols.contains(*name) {
+                return Some(name.to_string());
+            }
+
+            clones(symbols, name).filter(|(_, suffix)| {
+                preserved(suffix)
+            }).map(|(symbol, _)| symbol).min().cloned()
+        }).collect()
+    }
+
+    pub fn clones(&self, candidates: &[&str]) -> Vec<String> {
+        let symbols = match &self.symbols {
+            Some(symbols) => symbols,
+            None          => return Vec::new(),
+        };
+
+        let mut clones = candidates.iter().flat_map(|name| {
+            clones(symbols, name).filter(|(_, suffix)| !preserved(suffix))
+        }).map(|(symbol, _)| symbol.clone()).collect::<Vec<_>>();
+        clones.sort();
+
+        clones
+    }
+
+    pub fn tracepoint(&self, category: &str, name: &str) -> bool {
+        match self.tracefs {
+            Some(root) => root.join("events").join(category).join(name).exists(),
+            None       => true,
+        }
+    }
+}
+
+impl Report {
+    pub fn check(&self) -> Result<()> {
+        match self.skipped.iter().find(|skipped| skipped.required) {
+            Some(skipped) => Err(anyhow!("required program {} not attached: {}", skipped.program, skipped.reason)),
+            None          => Ok(()),
+        }
+    }
+}
+
+fn clones<'a>(symbols: &'a HashSet<String>, name: &'a str) -> impl Iterator<Item = (&'a String, &'a str)> {
+    symbols.iter().filter_map(move |symbol| {
+        match symbol.strip_prefix(name)? {
+            suffix if suffix.starts_with('.') => Some((symbol, suffix)),
+            _                                 => None,
+        }
+    })
+}
+
+// Only LTO renames and local aliases keep the original calling convention,
+// .isra and .constprop clones drop or rewrite arguments and .cold and .part
+// are fragments of the function.
+fn preserved(suffix: &str) -> bool {
+    suffix.starts_with(".llvm.") || suffix == ".localalias"
+}
+
+pub fn target(program: &str) -> Option<&'static Target> {
+    TARGETS.iter().find(|target| target.program == program)
+}
+
+const TRACEFS: &[&str] = &["/sys/kernel/tracing", "/sys/kernel/debug/tracing"];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbols() {
+        let kernel = Kernel {
+            symbols: Some([
+                "tcp_v4_connect",
+                "udp_recvmsg.isra.0",
+                "udp_recvmsg.cold",
+                "udpv6_recvmsg.constprop.0",
+                "udpv6_recvmsg.llvm.1234",
+                "tcp_close.localalias",
+                "tcp_close.part.0",
+                "tcp_closed",
+            ].iter().map(|name| name.to_string()).collect()),
+            tracefs: None,
+        };
+
+        let symbols = kernel.symbols(&["tcp_v4_connect", "udp_recvmsg", "udpv6_recvmsg", "tcp_close"]);
+        assert_eq!(symbols, vec!["tcp_v4_connect", "udpv6_recvmsg.llvm.1234", "tcp_close.localalias"]);
+
+        assert_eq!(kernel.clones(&["udp_recvmsg"]), vec!["udp_recvmsg.cold", "udp_recvmsg.isra.0"]);
+        assert_eq!(kernel.clones(&["tcp_close"]), vec!["tcp_close.part.0"]);
+        assert!(kernel.clones(&["tcp_v4_connect"]).is_empty());
+    }
+}
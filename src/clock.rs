@@ -0,0 +1,72 @@
+use std::io::Error;
+use std::mem::MaybeUninit;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use anyhow::Result;
+use libc::{clock_gettime, CLOCK_MONOTONIC};
+use log::{debug, error};
+use parking_lot::RwLock;
+use tokio::time::interval;
+
+pub struct Clock {
+    boot: RwLock<SystemTime>,
+}
+
+impl Clock {
+    pub fn new() -> Result<Self> {
+        let boot = RwLock::new(calibrate()?);
+        Ok(Self { boot })
+    }
+
+    pub fn spawn(self: Arc<Self>, period: Duration) {
+        tokio::spawn(async move {
+            match self.exec(period).await {
+                Ok(()) => debug!("clock finished"),
+                Err(e) => error!("clock failed: {:?}", e),
+            }
+        });
+    }
+
+    pub fn time(&self, ns: u64) -> SystemTime {
+        *self.boot.read() + Duration::from_nanos(ns)
+    }
+
+    async fn exec(&self, period: Duration) -> Result<()> {
+        let mut interval = interval(period);
+
+        loop {
+            interval.tick().await;
+
+            let boot = calibrate()?;
+            let prev = *self.boot.read();
+
+            let skew = match boot.duration_since(prev) {
+                Ok(skew) => skew,
+                Err(e)   => e.duration(),
+            };
+
+            debug!("clock skew {:?}", skew);
+
+            *self.boot.write() = boot;
+        }
+    }
+}
+
+fn calibrate() -> Result<SystemTime> {
+    let t0   = monotonic()?;
+    let now  = SystemTime::now();
+    let t1   = monotonic()?;
+    let mono = t0 + (t1 - t0) / 2;
+    Ok(now - mono)
+}
+
+pub fn monotonic() -> Result<Duration> {
+    let mut ts = MaybeUninit::uninit();
+
+    if unsafe { clock_gettime(CLOCK_MONOTONIC, ts.as_mut_ptr()) } != 0 {
+        return Err(Error::last_os_error().into());
+    }
+
+    let ts = unsafe { ts.assume_init() };
+    Ok(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
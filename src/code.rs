@@ -1,80 +1,384 @@
 use std::convert::TryFrom;
+use std::fmt;
 use std::future::Future;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::Arc;
+use std::time::Duration;
 use bytes::BytesMut;
-use anyhow::Result;
-use aya::Bpf;
+use anyhow::{anyhow, Result};
+use aya::{Bpf, BpfLoader, Btf, Endianness, Pod};
+use aya::maps::{Array, HashMap, MapRef, MapRefMut, PerCpuArray};
 use aya::maps::perf::AsyncPerfEventArray;
-use aya::programs::Program;
+use aya::programs::{Link, LinkRef, Program};
 use aya::util::online_cpus;
-use log::{debug, error};
-use tokio::sync::mpsc::{channel, Receiver};
-use crate::event::{Event, Exec, Sock};
+use log::{debug, error, warn};
+use tokio::select;
+use tokio::sync::mpsc::Receiver;
+use tokio::sync::watch;
+use tokio::time::interval;
+use crate::attach::{self, Attached, Hook, Kernel, Report, Skipped, Target};
+use crate::event::{Event, Exec, Live, Sock, State, UdpFlow, UdpKey};
+use crate::filter::{Filter, Prefix, Rules, Table};
+use crate::inspect::Bytecode;
+use crate::queue::{Policy, Queue};
+use crate::ring::{self, RingBuf};
+use crate::stats::Stats;
 
 pub struct Code {
-    bpf: Bpf,
+    bpf:       Bpf,
+    transport: Transport,
+    legacy:    bool,
+    links:     Vec<(String, LinkRef)>,
+    stop:      watch::Sender<bool>,
 }
 
+#[derive(Copy, Clone, Debug)]
+pub enum Transport {
+    Perf,
+    RingBuf(RawFd, usize),
+}
+
+pub struct Config {
+    pub capacity: usize,
+    pub pages:    Option<usize>,
+    pub buffers:  usize,
+    pub buffer:   usize,
+    pub policy:   Policy,
+}
+
+#[derive(Clone)]
+struct Channels {
+    exec:   Queue<Exec>,
+    sock:   Queue<Sock>,
+    state:  Queue<State>,
+    stats:  Arc<Stats>,
+    stop:   watch::Receiver<bool>,
+    legacy: bool,
+}
+
+pub type LiveMap = HashMap<MapRef, u64, Live>;
+
+pub type UdpMap = HashMap<MapRefMut, UdpKey, UdpFlow>;
+
+pub type LostMap = PerCpuArray<MapRef, u64>;
+
+pub const VMLINUX: &str = "/sys/kernel/btf/vmlinux";
+
+unsafe impl Pod for Live {}
+
+unsafe impl Pod for UdpKey {}
+
+unsafe impl Pod for UdpFlow {}
+
+unsafe impl Pod for Rules {}
+
+unsafe impl Pod for Prefix {}
+
 impl Code {
-    pub fn load(bytecode: &[u8]) -> Result<Self> {
-        let bpf = Bpf::load(&bytecode)?;
-        Ok(Self { bpf })
+    pub fn load(bytecode: &[u8], btf: Option<&Btf>) -> Result<Self> {
+        let object = Bytecode::inspect(bytecode)?;
+
+        for warning in object.warnings() {
+            debug!("bytecode: {}", warning);
+        }
+
+        object.check()?;
+
+        if object.core && btf.is_none() {
+            return Err(anyhow!("bytecode requires BTF but {} is missing, use --btf <file>", VMLINUX));
+        }
+
+        let legacy = object.meta.is_none();
+
+        if legacy {
+            warn!("bytecode has no meta section, decoding unversioned events");
+        }
+
+        let bpf = BpfLoader::new().btf(btf).load(bytecode)?;
+        let fd  = bpf.map("events")?.as_raw_fd();
+
+        let transport = match ring::size(fd)? {
+            Some(size) => Transport::RingBuf(fd, size),
+            None       => Transport::Perf,
+        };
+
+        let (stop, _) = watch::channel(false);
+        let links     = Vec::new();
+
+        Ok(Self { bpf, transport, legacy, links, stop })
     }
 
-    pub fn exec(&mut self) -> Result<(Receiver<Exec>, Receiver<Sock>)> {
-        let events = self.bpf.map_mut("events")?;
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    pub fn filter(&mut self, filter: &Filter) -> Result<()> {
+        if filter.is_empty() {
+            return Ok(());
+        }
+
+        let map = |name| self.bpf.map_mut(name).map_err(|e| {
+            anyhow!("bytecode does not support filtering: {}", e)
+        });
+
+        let mut rules    = Array::<_, Rules>::try_from(map("rules")?)?;
+        let mut ports    = HashMap::<_, u32, u32>::try_from(map("ports")?)?;
+        let mut prefixes = Array::<_, Prefix>::try_from(map("prefixes")?)?;
+
+        filter.populate(&mut rules, &mut ports, &mut prefixes)
+    }
+
+    pub fn exec(&mut self, config: &Config, stats: Arc<Stats>) -> Result<(Receiver<Exec>, Receiver<Sock>, Receiver<State>)> {
+        let Config { capacity, policy, .. } = *config;
+
+        let (tx0, rx0) = Queue::new("exec", capacity, policy, stats.clone());
+        let (tx1, rx1) = Queue::new("sock", capacity, policy, stats.clone());
+        let (tx2, rx2) = Queue::new("state", capacity, policy, stats.clone());
+
+        let channels = Channels {
+            exec:   tx0,
+            sock:   tx1,
+            state:  tx2,
+            stats,
+            stop:   self.stop.subscribe(),
+            legacy: self.legacy,
+        };
+
+        match self.transport {
+            Transport::Perf              => self.perf(config, channels)?,
+            Transport::RingBuf(fd, size) => {
+                let lost = PerCpuArray::try_from(self.bpf.map("lost")?)?;
+                ringbuf(RingBuf::open(fd, size)?, lost, channels);
+            }
+        }
+
+        Ok((rx0, rx1, rx2))
+    }
+
+    pub fn attach(&mut self, kernel: &Kernel) -> Result<Report> {
+        let names = self.bpf.programs().map(|p| {
+            p.name().to_owned()
+        }).collect::<Vec<_>>();
+
+        let mut report = Report::default();
+
+        for name in names {
+            let target   = attach::target(&name);
+            let required = target.map(|t| t.required).unwrap_or(false);
+
+            match self.program(&name, target, kernel) {
+                Ok((target, link)) => {
+                    self.links.push((name.clone(), link));
+                    report.attached.push(Attached {
+                        program: name,
+                        target,
+                    });
+                }
+                Err(e)             => report.skipped.push(Skipped {
+                    program: name,
+                    reason:  e.to_string(),
+                    required,
+                }),
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn program(&mut self, name: &str, target: Option<&Target>, kernel: &Kernel) -> Result<(String, LinkRef)> {
+        let prog = self.bpf.program_mut(name)?;
+        prog.load()?;
+
+        debug!("loaded {}", name);
+
+        match (prog, target.map(|t| &t.hook)) {
+            (Program::KProbe(kprobe), hook) => {
+                let candidates = match hook {
+                    Some(Hook::KProbe(symbols)) => symbols.to_vec(),
+                    _                           => vec![name],
+                };
 
+                let mut result = match kernel.clones(&candidates)[..] {
+                    []         => Err(anyhow!("symbol not found: {}", candidates.join(", "))),
+                    ref clones => Err(anyhow!("symbol not found: {}, {} change the calling convention", candidates.join(", "), clones.join(", "))),
+                };
+
+                for symbol in kernel.symbols(&candidates) {
+                    match kprobe.attach(&symbol, 0) {
+                        Ok(link) => return Ok((symbol, link)),
+                        Err(e)   => result = Err(anyhow!("{}: {}", symbol, e)),
+                    }
+                }
+
+                result
+            }
+            (Program::TracePoint(trace), hook) => {
+                let (category, event) = match hook {
+                    Some(Hook::TracePoint(category, event)) => (*category, *event),
+                    _                                       => name.split_once('/').ok_or_else(|| {
+                        anyhow!("invalid tracepoint: {}", name)
+                    })?,
+                };
+
+                if !kernel.tracepoint(category, event) {
+                    return Err(anyhow!("tracepoint not found: {}/{}", category, event));
+                }
+
+                let link = trace.attach(category, event)?;
+
+                Ok((format!("{}/{}", category, event), link))
+            }
+            _ => Err(anyhow!("unsupported program type")),
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.send(true).ok();
+
+        for (name, mut link) in self.links.drain(..) {
+            match link.detach() {
+                Ok(()) => debug!("detached {}", name),
+                Err(e) => warn!("detach {} failed: {}", name, e),
+            }
+        }
+    }
+
+    fn perf(&mut self, config: &Config, channels: Channels) -> Result<()> {
+        let events = self.bpf.map_mut("events")?;
         let mut events = AsyncPerfEventArray::try_from(events)?;
-        let (tx0, rx0) = channel(1024);
-        let (tx1, rx1) = channel(1024);
 
         for cpu in online_cpus()? {
-            let mut buf  = events.open(cpu, None)?;
-            let mut bufs = (0..10).map(|_| {
-                BytesMut::with_capacity(1024)
+            let mut buf  = events.open(cpu, config.pages)?;
+            let mut bufs = (0..config.buffers).map(|_| {
+                BytesMut::with_capacity(config.buffer)
             }).collect::<Vec<_>>();
 
-            let tx0 = tx0.clone();
-            let tx1 = tx1.clone();
+            let mut channels = channels.clone();
 
             spawn(async move {
                 loop {
-                    let events = buf.read_events(&mut bufs).await?;
+                    let events = select! {
+                        events = buf.read_events(&mut bufs) => events?,
+                        _      = channels.stop.changed()    => break,
+                    };
+
+                    if events.lost > 0 {
+                        warn!("lost {} events on cpu {}", events.lost, cpu);
+                        channels.stats.lost(cpu, u64::try_from(events.lost)?);
+                    }
+
                     for buf in bufs.iter_mut().take(events.read) {
-                        match Event::read(&buf[..]) {
-                            Ok(Event::Exec(e)) => tx0.send(e).await?,
-                            Ok(Event::Sock(s)) => tx1.send(s).await?,
-                            Err(e)             => error!("{}", e),
-                        };
+                        channels.send(&buf[..]).await?;
                     }
                 }
+                Ok(())
             });
         }
 
-        let names = self.bpf.programs().map(|p| {
-            p.name().to_owned()
-        }).collect::<Vec<_>>();
+        Ok(())
+    }
 
-        for name in names {
-            let prog = self.bpf.program_mut(&name)?;
-            prog.load()?;
+    pub fn live(&self) -> Result<LiveMap> {
+        let live = self.bpf.map("live")?;
+        Ok(HashMap::try_from(live)?)
+    }
 
-            debug!("loaded {}", name);
+    pub fn udp(&self) -> Result<Option<UdpMap>> {
+        match self.bpf.map_mut("udp") {
+            Ok(udp) => Ok(Some(HashMap::try_from(udp)?)),
+            Err(_)  => Ok(None),
+        }
+    }
+}
 
-            if let Program::KProbe(kprobe) = prog {
-                let func = match name.as_str() {
-                    "call-tcp-connect" => "tcp_v4_connect",
-                    "exit-tcp-connect" => "tcp_v4_connect",
-                    name               => name,
-                };
-                kprobe.attach(func, 0)?;
-            } else if let Program::TracePoint(trace) = prog {
-                let index = name.find('/').unwrap_or(0);
-                let (category, name) = name.split_at(index);
-                trace.attach(category, name)?;
+pub fn btf(path: Option<&str>) -> Result<Option<Btf>> {
+    match path {
+        Some(path) => match Btf::parse_file(path, Endianness::default()) {
+            Ok(btf) => Ok(Some(btf)),
+            Err(e)  => Err(anyhow!("failed to load BTF from {}: {}", path, e)),
+        },
+        None       => Ok(Btf::from_sys_fs().ok()),
+    }
+}
+
+fn ringbuf(mut ring: RingBuf, lost: LostMap, mut channels: Channels) {
+    let stats    = channels.stats.clone();
+    let mut stop = channels.stop.clone();
+
+    spawn(async move {
+        let mut bufs = Vec::new();
+        loop {
+            select! {
+                result = ring.read_events(&mut bufs) => result?,
+                _      = channels.stop.changed()     => break,
+            };
+            for buf in &bufs {
+                channels.send(&buf[..]).await?;
             }
         }
+        Ok(())
+    });
+
+    spawn(async move {
+        let mut interval = interval(Duration::from_secs(10));
+        let mut last     = Vec::new();
+
+        loop {
+            select! {
+                _ = interval.tick() => (),
+                _ = stop.changed()  => break,
+            };
+
+            let counts = lost.get(&0, 0)?;
+            last.resize(counts.len(), 0);
 
-        Ok((rx0, rx1))
+            for (cpu, (count, seen)) in counts.iter().zip(last.iter_mut()).enumerate() {
+                if *count > *seen {
+                    warn!("lost {} events on cpu {}", count - *seen, cpu);
+                    stats.lost(u32::try_from(cpu)?, count - *seen);
+                    *seen = *count;
+                }
+            }
+        }
+        Ok(())
+    });
+}
+
+impl<V: Pod> Table<u32, V> for Array<MapRefMut, V> {
+    fn set(&mut self, index: u32, value: V) -> Result<()> {
+        Ok(Array::set(self, index, value, 0)?)
+    }
+}
+
+impl<K: Pod, V: Pod> Table<K, V> for HashMap<MapRefMut, K, V> {
+    fn set(&mut self, key: K, value: V) -> Result<()> {
+        Ok(self.insert(key, value, 0)?)
+    }
+}
+
+impl Channels {
+    async fn send(&self, buf: &[u8]) -> Result<()> {
+        let event = match self.legacy {
+            true  => Event::unversioned(buf),
+            false => Event::read(buf),
+        };
+
+        match event {
+            Ok(Event::Exec(e))  => self.exec.send(e).await?,
+            Ok(Event::Sock(s))  => self.sock.send(s).await?,
+            Ok(Event::State(s)) => self.state.send(s).await?,
+            Err(e)              => error!("{}", e),
+        };
+        Ok(())
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Perf          => write!(f, "perf"),
+            Self::RingBuf(_, _) => write!(f, "ringbuf"),
+        }
     }
 }
 
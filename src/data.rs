@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::fmt;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
@@ -8,10 +9,17 @@ use serde::Serialize;
 #[derive(Clone, Debug, Serialize)]
 pub struct Process {
     pub pid:       pid_t,
+    pub ppid:      Option<pid_t>,
     pub command:   Vec<String>,
+    pub exe:       Option<String>,
+    pub comm:      String,
+    pub netns:     Option<u64>,
+    pub uid:       Option<u32>,
+    pub gid:       Option<u32>,
     pub container: Option<Container>,
     pub pod:       Option<Pod>,
     pub status:    Status,
+    pub resolved:  bool,
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -34,16 +42,140 @@ pub enum Status {
     Dead,
 }
 
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+pub enum Proto {
+    Tcp,
+    Udp,
+}
+
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+pub enum Errno {
+    ConnRefused,
+    ConnReset,
+    NetUnreach,
+    HostUnreach,
+    TimedOut,
+    AddrInUse,
+    AddrNotAvail,
+    Access,
+    Perm,
+    Other(i32),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize)]
+pub enum Direction {
+    Client,
+    Server,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize)]
+pub enum TcpState {
+    Established,
+    SynSent,
+    SynRecv,
+    FinWait1,
+    FinWait2,
+    TimeWait,
+    Close,
+    CloseWait,
+    LastAck,
+    Listen,
+    Closing,
+    NewSynRecv,
+    Unknown(u32),
+}
+
+#[derive(Copy, Clone, Debug, Default, Serialize)]
+pub struct Buildup {
+    pub time_wait:  u32,
+    pub close_wait: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Flow {
+    pub start:     SystemTime,
+    pub end:       SystemTime,
+    pub duration:  Duration,
+    pub direction: Direction,
+}
+
 #[derive(Debug, Serialize)]
 pub struct Record {
-    pub timestamp: SystemTime,
-    pub event:     String,
-    pub src:       SocketAddr,
-    pub dst:       SocketAddr,
-    pub process:   Arc<Process>,
-    pub hostname:  Arc<String>,
-    pub rx:        u32,
-    pub tx:        u32,
-    pub srtt:      Duration,
-    pub retx:      u32,
+    pub timestamp:         SystemTime,
+    pub event:             String,
+    pub proto:             Proto,
+    pub src:               SocketAddr,
+    pub dst:               SocketAddr,
+    pub process:           Arc<Process>,
+    pub ancestry:          Vec<Arc<Process>>,
+    pub netns:             u64,
+    pub pod:               Option<Pod>,
+    pub tid:               Option<pid_t>,
+    pub hostname:          Arc<String>,
+    pub rx:                u64,
+    pub tx:                u64,
+    pub rx_delta:          u64,
+    pub tx_delta:          u64,
+    pub rx_pkts:           u32,
+    pub tx_pkts:           u32,
+    pub srtt:              Duration,
+    pub retx:              u32,
+    pub error:             Option<Errno>,
+    pub handshake_latency: Option<Duration>,
+    pub buildup:           Option<Buildup>,
+    pub flow:              Option<Flow>,
+}
+
+impl From<i32> for Errno {
+    fn from(errno: i32) -> Self {
+        match errno {
+            libc::ECONNREFUSED  => Self::ConnRefused,
+            libc::ECONNRESET    => Self::ConnReset,
+            libc::ENETUNREACH   => Self::NetUnreach,
+            libc::EHOSTUNREACH  => Self::HostUnreach,
+            libc::ETIMEDOUT     => Self::TimedOut,
+            libc::EADDRINUSE    => Self::AddrInUse,
+            libc::EADDRNOTAVAIL => Self::AddrNotAvail,
+            libc::EACCES        => Self::Access,
+            libc::EPERM         => Self::Perm,
+            errno               => Self::Other(errno),
+        }
+    }
+}
+
+impl From<u32> for TcpState {
+    fn from(state: u32) -> Self {
+        match state {
+            1     => Self::Established,
+            2     => Self::SynSent,
+            3     => Self::SynRecv,
+            4     => Self::FinWait1,
+            5     => Self::FinWait2,
+            6     => Self::TimeWait,
+            7     => Self::Close,
+            8     => Self::CloseWait,
+            9     => Self::LastAck,
+            10    => Self::Listen,
+            11    => Self::Closing,
+            12    => Self::NewSynRecv,
+            state => Self::Unknown(state),
+        }
+    }
+}
+
+impl fmt::Display for Errno {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConnRefused  => write!(f, "ECONNREFUSED"),
+            Self::ConnReset    => write!(f, "ECONNRESET"),
+            Self::NetUnreach   => write!(f, "ENETUNREACH"),
+            Self::HostUnreach  => write!(f, "EHOSTUNREACH"),
+            Self::TimedOut     => write!(f, "ETIMEDOUT"),
+            Self::AddrInUse    => write!(f, "EADDRINUSE"),
+            Self::AddrNotAvail => write!(f, "EADDRNOTAVAIL"),
+            Self::Access       => write!(f, "EACCES"),
+            Self::Perm         => write!(f, "EPERM"),
+            Self::Other(errno) => write!(f, "errno {}", errno),
+        }
+    }
 }
@@ -0,0 +1,130 @@
+use std::convert::TryInto;
+use std::str;
+use anyhow::{anyhow, Result};
+
+pub struct Elf<'a> {
+    pub machine: u16,
+    sections:    Vec<Section<'a>>,
+}
+
+pub struct Section<'a> {
+    pub name: &'a str,
+    pub kind: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> Elf<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        if data.get(..4) != Some(b"\x7fELF") {
+            return Err(anyhow!("not an ELF object"));
+        }
+
+        if data.get(4) != Some(&ELFCLASS64) || data.get(5) != Some(&ELFDATA2LSB) {
+            return Err(anyhow!("unsupported ELF class or byte order"));
+        }
+
+        let machine   = u16(data, 0x12)?;
+        let shoff     = u64(data, 0x28)? as usize;
+        let shentsize = u16(data, 0x3a)? as usize;
+        let shnum     = u16(data, 0x3c)? as usize;
+        let shstrndx  = u16(data, 0x3e)? as usize;
+
+        let headers = (0..shnum).map(|index| {
+            let offset = index.checked_mul(shentsize).and_then(|n| n.checked_add(shoff));
+            let header = offset.and_then(|offset| data.get(offset..)).ok_or_else(|| {
+                anyhow!("truncated ELF object")
+            })?;
+            Ok((
+                u32(header, 0x00)? as usize,
+                u32(header, 0x04)?,
+                u64(header, 0x18)? as usize,
+                u64(header, 0x20)? as usize,
+            ))
+        }).collect::<Result<Vec<_>>>()?;
+
+        let strtab = match headers.get(shstrndx) {
+            Some(&(_, _, offset, size)) => slice(data, offset, size)?,
+            None                        => return Err(anyhow!("missing section name table")),
+        };
+
+        let sections = headers.into_iter().map(|(name, kind, offset, size)| {
+            let name = strtab.get(name..).unwrap_or_default();
+            let end  = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+            let data = match kind {
+                SHT_NOBITS => &[][..],
+                _          => slice(data, offset, size)?,
+            };
+
+            Ok(Section {
+                name: str::from_utf8(&name[..end])?,
+                kind,
+                data,
+            })
+        }).collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { machine, sections })
+    }
+
+    pub fn sections(&self) -> impl Iterator<Item = &Section<'a>> {
+        self.sections.iter()
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section<'a>> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+}
+
+fn slice(data: &[u8], offset: usize, size: usize) -> Result<&[u8]> {
+    offset.checked_add(size).and_then(|end| data.get(offset..end)).ok_or_else(|| {
+        anyhow!("truncated ELF object")
+    })
+}
+
+fn u16(data: &[u8], offset: usize) -> Result<u16> {
+    Ok(u16::from_le_bytes(slice(data, offset, 2)?.try_into()?))
+}
+
+fn u32(data: &[u8], offset: usize) -> Result<u32> {
+    Ok(u32::from_le_bytes(slice(data, offset, 4)?.try_into()?))
+}
+
+fn u64(data: &[u8], offset: usize) -> Result<u64> {
+    Ok(u64::from_le_bytes(slice(data, offset, 8)?.try_into()?))
+}
+
+const ELFCLASS64:  u8  = 2;
+const ELFDATA2LSB: u8  = 1;
+const SHT_NOBITS:  u32 = 8;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overflowing_headers() {
+        for &(shoff, shentsize) in &[(u64::MAX - 8, 64u16), (64, u16::MAX), (u64::MAX, 0)] {
+            let mut data = vec![0u8; 64];
+            data[..6].copy_from_slice(b"\x7fELF\x02\x01");
+            data[0x28..0x30].copy_from_slice(&shoff.to_le_bytes());
+            data[0x3a..0x3c].copy_from_slice(&shentsize.to_le_bytes());
+            data[0x3c..0x3e].copy_from_slice(&4u16.to_le_bytes());
+
+            let error = Elf::parse(&data).err().map(|e| e.to_string());
+            assert_eq!(error.as_deref(), Some("truncated ELF object"));
+        }
+    }
+
+    #[test]
+    fn overflowing_sections() {
+        let mut data = vec![0u8; 64 + 64];
+        data[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        data[0x28..0x30].copy_from_slice(&64u64.to_le_bytes());
+        data[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+        data[0x3c..0x3e].copy_from_slice(&1u16.to_le_bytes());
+        data[64 + 0x18..64 + 0x20].copy_from_slice(&8u64.to_le_bytes());
+        data[64 + 0x20..64 + 0x28].copy_from_slice(&u64::MAX.to_le_bytes());
+
+        let error = Elf::parse(&data).err().map(|e| e.to_string());
+        assert_eq!(error.as_deref(), Some("truncated ELF object"));
+    }
+}
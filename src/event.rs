@@ -1,32 +1,90 @@
 use std::convert::{TryFrom, TryInto};
 use std::mem::size_of;
-use std::net::{Ipv4Addr, SocketAddr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
 use anyhow::{anyhow, Result};
-use bytemuck::{try_from_bytes, Pod, Zeroable};
-use libc::pid_t;
+use bytemuck::{bytes_of_mut, Pod, Zeroable};
+use libc::{pid_t, IPPROTO_TCP, IPPROTO_UDP};
+use crate::clock::monotonic;
+use crate::data::{Buildup, Direction, Errno, Proto, TcpState};
 
 #[derive(Debug)]
 pub enum Event {
     Exec(Exec),
     Sock(Sock),
+    State(State),
 }
 
 #[derive(Debug)]
 pub enum Exec {
-    Exec(pid_t),
+    Exec(pid_t, Option<Command>),
+    Fork(pid_t, pid_t),
     Exit(pid_t),
 }
 
+#[derive(Debug)]
+pub struct Command {
+    pub ppid:     pid_t,
+    pub cgroup:   u64,
+    pub netns:    u64,
+    pub task:     Option<Task>,
+    pub filename: String,
+    pub argv:     Vec<String>,
+}
+
 #[derive(Debug)]
 pub struct Sock {
-    pub call: Call,
-    pub pid:  pid_t,
-    pub src:  SocketAddr,
-    pub dst:  SocketAddr,
-    pub rx:   u32,
-    pub tx:   u32,
-    pub srtt: u32,
-    pub retx: u32,
+    pub call:              Call,
+    pub pid:               pid_t,
+    pub ts:                u64,
+    pub cgroup:            u64,
+    pub netns:             u64,
+    pub task:              Option<Task>,
+    pub proto:             Proto,
+    pub src:               SocketAddr,
+    pub dst:               SocketAddr,
+    pub rx:                u64,
+    pub tx:                u64,
+    pub rx_delta:          u64,
+    pub tx_delta:          u64,
+    pub rx_pkts:           u32,
+    pub tx_pkts:           u32,
+    pub srtt:              u32,
+    pub retx:              u32,
+    pub error:             Option<Errno>,
+    pub handshake_latency: Option<Duration>,
+    pub buildup:           Option<Buildup>,
+    pub flow:              Option<Span>,
+}
+
+#[derive(Debug)]
+pub struct State {
+    pub pid:    pid_t,
+    pub ts:     u64,
+    pub cgroup: u64,
+    pub netns:  u64,
+    pub task:   Option<Task>,
+    pub src:    SocketAddr,
+    pub dst:    SocketAddr,
+    pub sk:     u64,
+    pub start:  u64,
+    pub old:    TcpState,
+    pub new:    TcpState,
+    pub error:  Option<Errno>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Task {
+    pub tid:  pid_t,
+    pub uid:  u32,
+    pub gid:  u32,
+    pub comm: String,
+}
+
+#[derive(Debug)]
+pub struct Span {
+    pub start:     u64,
+    pub direction: Direction,
 }
 
 #[derive(Debug)]
@@ -34,15 +92,57 @@ pub enum Call {
     Accept,
     Close,
     Connect,
+    ConnectFailed,
+    Udp,
+    Update,
+    Flow,
+    SynTimeout,
+    Buildup,
+    Retransmit,
+    SendReset,
+    ReceiveReset,
+    Listen,
+    Established,
+    Handshake,
 }
 
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 #[repr(C)]
 struct Header {
+    version: u16,
+    size:    u16,
+    length:  u32,
+    kind:    u32,
+    pid:     u32,
+    ts:      u64,
+}
+
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+struct Unversioned {
     kind: u32,
     pid:  u32,
 }
 
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+struct RawTask {
+    tid:  u32,
+    uid:  u32,
+    gid:  u32,
+    pad:  u32,
+    comm: [u8; 16],
+}
+
+struct Meta {
+    version: u16,
+    pid:     pid_t,
+    ts:      u64,
+    cgroup:  u64,
+    netns:   u64,
+    task:    Option<Task>,
+}
+
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 #[repr(C)]
 struct Sock4 {
@@ -53,62 +153,766 @@ struct Sock4 {
     dport: u32,
 }
 
-const EXEC:    u32 = 0;
-const EXIT:    u32 = 1;
-const CONNECT: u32 = 2;
-const ACCEPT:  u32 = 3;
-const CLOSE:   u32 = 4;
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+struct Sock6 {
+    proto: u32,
+    saddr: [u8; 16],
+    sport: u32,
+    daddr: [u8; 16],
+    dport: u32,
+}
+
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct Live {
+    pid:    u32,
+    socket: Sock6,
+    cgroup: u64,
+    netns:  u64,
+    start:  u64,
+    rx:     u64,
+    tx:     u64,
+    srtt:   u32,
+    retx:   u32,
+    task:   RawTask,
+}
+
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct UdpKey {
+    pid:    u32,
+    socket: Sock6,
+}
+
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct UdpFlow {
+    cgroup:  u64,
+    netns:   u64,
+    ts:      u64,
+    rx:      u64,
+    tx:      u64,
+    rx_pkts: u32,
+    tx_pkts: u32,
+    task:    RawTask,
+}
+
+pub const VERSION: u32 = 5;
+
+const FILENAME_SIZE: usize = 128;
+const ARGV_SIZE:     usize = 256;
+
+const EXEC:            u32 = 0;
+const EXIT:            u32 = 1;
+const CONNECT:         u32 = 2;
+const ACCEPT:          u32 = 3;
+const CLOSE:           u32 = 4;
+const CONNECT6:        u32 = 5;
+const ACCEPT6:         u32 = 6;
+const CLOSE6:          u32 = 7;
+const CONNECT_FAILED:  u32 = 10;
+const CONNECT6_FAILED: u32 = 11;
+const STATE:           u32 = 12;
+const STATE6:          u32 = 13;
+const RETRANSMIT:      u32 = 14;
+const RETRANSMIT6:     u32 = 15;
+const SEND_RESET:      u32 = 16;
+const SEND_RESET6:     u32 = 17;
+const RECEIVE_RESET:   u32 = 18;
+const RECEIVE_RESET6:  u32 = 19;
+const FORK:            u32 = 20;
+
+pub const KINDS: u32 = FORK + 1;
 
 impl Event {
     pub fn read(buf: &[u8]) -> Result<Self> {
-        let (head, data) = buf.split_at(size_of::<Header>());
+        let mut buf = Reader::new(buf);
+
+        let head = buf.read::<Header>().map_err(|e| {
+            anyhow!("invalid header: {}", e)
+        })?;
+
+        if head.version == 0 || u32::from(head.version) > VERSION {
+            return Err(anyhow!("unsupported version: {}", head.version));
+        }
+
+        let size = match head.size {
+            0    => legacy(head.version)?,
+            size => usize::from(size).checked_sub(size_of::<Header>()).ok_or_else(|| {
+                anyhow!("invalid header size: {}", size)
+            })?,
+        };
+
+        let mut common = buf.take(size).map(Reader::new).map_err(|e| {
+            anyhow!("invalid header: {}", e)
+        })?;
+
+        let cgroup = match head.version {
+            1 => 0,
+            _ => common.read::<u64>()?,
+        };
+
+        let task = match head.version {
+            1 | 2 => None,
+            _     => Some(common.read::<RawTask>()?.read()?),
+        };
+
+        let netns = match head.version {
+            1..=3 => 0,
+            _     => common.read::<u64>()?,
+        };
+
+        let data = buf.take(head.length.try_into()?).map_err(|e| {
+            anyhow!("invalid payload: {}", e)
+        })?;
+        let mut data = Reader::new(data);
+
+        let version = head.version;
+        let pid     = head.pid.try_into()?;
+        let ts      = head.ts;
+        let meta    = Meta { version, pid, ts, cgroup, netns, task };
 
-        let head = try_from_bytes::<Header>(head).map_err(|e| {
+        Ok(match head.kind {
+            EXEC            => Event::Exec(Exec::Exec(pid, Command::read(meta, data)?)),
+            FORK            => Event::Exec(Exec::Fork(pid, data.read::<u32>()?.try_into()?)),
+            EXIT            => Event::Exec(Exec::Exit(pid)),
+            ACCEPT          => Event::Sock(Sock::read4(Call::Accept, meta, data)?),
+            CLOSE           => Event::Sock(Sock::read4(Call::Close, meta, data)?),
+            CONNECT         => Event::Sock(Sock::read4(Call::Connect, meta, data)?),
+            ACCEPT6         => Event::Sock(Sock::read6(Call::Accept, meta, data)?),
+            CLOSE6          => Event::Sock(Sock::read6(Call::Close, meta, data)?),
+            CONNECT6        => Event::Sock(Sock::read6(Call::Connect, meta, data)?),
+            CONNECT_FAILED  => Event::Sock(Sock::read4(Call::ConnectFailed, meta, data)?),
+            CONNECT6_FAILED => Event::Sock(Sock::read6(Call::ConnectFailed, meta, data)?),
+            STATE           => Event::State(State::read4(meta, data)?),
+            STATE6          => Event::State(State::read6(meta, data)?),
+            RETRANSMIT      => Event::Sock(Sock::read4(Call::Retransmit, meta, data)?),
+            RETRANSMIT6     => Event::Sock(Sock::read6(Call::Retransmit, meta, data)?),
+            SEND_RESET      => Event::Sock(Sock::read4(Call::SendReset, meta, data)?),
+            SEND_RESET6     => Event::Sock(Sock::read6(Call::SendReset, meta, data)?),
+            RECEIVE_RESET   => Event::Sock(Sock::read4(Call::ReceiveReset, meta, data)?),
+            RECEIVE_RESET6  => Event::Sock(Sock::read6(Call::ReceiveReset, meta, data)?),
+            kind            => return Err(anyhow!("invalid event: {}", kind)),
+        })
+    }
+}
+
+impl Event {
+    pub fn unversioned(buf: &[u8]) -> Result<Self> {
+        let mut buf = Reader::new(buf);
+
+        let head = buf.read::<Unversioned>().map_err(|e| {
             anyhow!("invalid header: {}", e)
         })?;
 
-        let pid = head.pid.try_into()?;
+        let pid  = head.pid.try_into()?;
+        let ts   = u64::try_from(monotonic()?.as_nanos())?;
+        let meta = Meta { version: 0, pid, ts, cgroup: 0, netns: 0, task: None };
 
         Ok(match head.kind {
-            EXEC    => Event::Exec(Exec::Exec(pid)),
+            EXEC    => Event::Exec(Exec::Exec(pid, None)),
             EXIT    => Event::Exec(Exec::Exit(pid)),
-            ACCEPT  => Event::Sock(Sock::read(Call::Accept, pid, data)?),
-            CLOSE   => Event::Sock(Sock::read(Call::Close, pid, data)?),
-            CONNECT => Event::Sock(Sock::read(Call::Connect, pid, data)?),
+            ACCEPT  => Event::Sock(Sock::read4(Call::Accept, meta, buf)?),
+            CLOSE   => Event::Sock(Sock::read4(Call::Close, meta, buf)?),
+            CONNECT => Event::Sock(Sock::read4(Call::Connect, meta, buf)?),
             kind    => return Err(anyhow!("invalid event: {}", kind)),
         })
     }
 }
 
 impl Sock {
-    pub fn read(call: Call, pid: pid_t, buf: &[u8]) -> Result<Self> {
-        let (data, tail) = buf.split_at(size_of::<Sock4>());
+    fn read4(call: Call, meta: Meta, mut buf: Reader) -> Result<Self> {
+        let sock4 = buf.read::<Sock4>().map_err(|e| {
+            anyhow!("invalid sock4: {}", e)
+        })?;
+
+        let (proto, src, dst) = sock4.read()?;
+
+        Self::read(call, meta, proto, src, dst, buf)
+    }
+
+    fn read6(call: Call, meta: Meta, mut buf: Reader) -> Result<Self> {
+        let sock6 = buf.read::<Sock6>().map_err(|e| {
+            anyhow!("invalid sock6: {}", e)
+        })?;
+
+        let (proto, src, dst) = sock6.read()?;
+
+        Self::read(call, meta, proto, src, dst, buf)
+    }
+
+    pub fn live(live: &Live, ts: u64) -> Result<Self> {
+        let pid = live.pid.try_into()?;
+        let (proto, src, dst) = live.socket.read()?;
+
+        Ok(Sock {
+            call:              Call::Update,
+            cgroup:            live.cgroup,
+            netns:             live.netns,
+            task:              Some(live.task.read()?),
+            pid, ts, proto, src, dst,
+            rx:                live.rx,
+            tx:                live.tx,
+            rx_delta:          live.rx,
+            tx_delta:          live.tx,
+            rx_pkts:           0,
+            tx_pkts:           0,
+            srtt:              live.srtt,
+            retx:              live.retx,
+            error:             None,
+            handshake_latency: None,
+            buildup:           None,
+            flow:              None,
+        })
+    }
+
+    pub fn udp(key: &UdpKey, flow: &UdpFlow) -> Result<Self> {
+        let pid = key.pid.try_into()?;
+        let (proto, src, dst) = key.socket.read()?;
+
+        Ok(Sock {
+            call:              Call::Udp,
+            ts:                flow.ts,
+            cgroup:            flow.cgroup,
+            netns:             flow.netns,
+            task:              Some(flow.task.read()?),
+            pid, proto, src, dst,
+            rx:                flow.rx,
+            tx:                flow.tx,
+            rx_delta:          flow.rx,
+            tx_delta:          flow.tx,
+            rx_pkts:           flow.rx_pkts,
+            tx_pkts:           flow.tx_pkts,
+            srtt:              0,
+            retx:              0,
+            error:             None,
+            handshake_latency: None,
+            buildup:           None,
+            flow:              None,
+        })
+    }
+
+    fn read(call: Call, meta: Meta, proto: Proto, src: SocketAddr, dst: SocketAddr, mut tail: Reader) -> Result<Self> {
+        let Meta { version, pid, ts, cgroup, netns, task } = meta;
+
+        let mut rx    = 0;
+        let mut tx    = 0;
+        let mut srtt  = 0;
+        let mut retx  = 0;
+        let mut error = None;
+
+        match call {
+            Call::Close if version == 0 => {
+                rx   = tail.read::<u32>()?.into();
+                tx   = tail.read::<u32>()?.into();
+                srtt = tail.read()?;
+                retx = tail.read()?;
+            }
+            Call::Close => {
+                rx   = tail.read()?;
+                tx   = tail.read()?;
+                srtt = tail.read()?;
+                retx = tail.read()?;
+            }
+            Call::ConnectFailed => {
+                let errno = tail.read::<i32>()?;
+                error = Some(Errno::from(errno.wrapping_neg()));
+            }
+            Call::Retransmit => {
+                retx = 1;
+            }
+            _ => (),
+        }
 
-        let sock4 = try_from_bytes::<Sock4>(data).map_err(|e| {
+        Ok(Sock {
+            call, pid, ts, cgroup, netns, task, proto, src, dst,
+            rx, tx, rx_delta: rx, tx_delta: tx,
+            srtt, retx, error,
+            rx_pkts:           0,
+            tx_pkts:           0,
+            handshake_latency: None,
+            buildup:           None,
+            flow:              None,
+        })
+    }
+}
+
+impl Command {
+    fn read(meta: Meta, mut buf: Reader) -> Result<Option<Self>> {
+        let Meta { cgroup, netns, task, .. } = meta;
+
+        if buf.len() == 0 {
+            return Ok(None);
+        }
+
+        let ppid     = buf.read::<u32>()?.try_into()?;
+        let len      = usize::try_from(buf.read::<u32>()?)?;
+        let filename = buf.take(FILENAME_SIZE)?;
+        let argv     = buf.take(ARGV_SIZE)?;
+
+        let filename = cstr(filename);
+        let argv     = argv[..len.min(ARGV_SIZE)].split(|&b| b == 0).filter(|arg| {
+            !arg.is_empty()
+        }).map(|arg| String::from_utf8_lossy(arg).into_owned()).collect();
+
+        Ok(Some(Command { ppid, cgroup, netns, task, filename, argv }))
+    }
+}
+
+impl State {
+    fn read4(meta: Meta, mut buf: Reader) -> Result<Self> {
+        let sock4 = buf.read::<Sock4>().map_err(|e| {
             anyhow!("invalid sock4: {}", e)
         })?;
 
-        let saddr = Ipv4Addr::from(sock4.saddr.to_be());
-        let sport = u16::try_from(sock4.sport)?;
-        let daddr = Ipv4Addr::from(sock4.daddr.to_be());
-        let dport = u16::try_from(sock4.dport)?;
+        let (_, src, dst) = sock4.read()?;
+
+        Self::read(meta, src, dst, buf)
+    }
+
+    fn read6(meta: Meta, mut buf: Reader) -> Result<Self> {
+        let sock6 = buf.read::<Sock6>().map_err(|e| {
+            anyhow!("invalid sock6: {}", e)
+        })?;
+
+        let (_, src, dst) = sock6.read()?;
+
+        Self::read(meta, src, dst, buf)
+    }
+
+    fn read(meta: Meta, src: SocketAddr, dst: SocketAddr, mut tail: Reader) -> Result<Self> {
+        let Meta { pid, ts, cgroup, netns, task, .. } = meta;
+
+        let sk    = tail.read::<u64>()?;
+        let start = tail.read::<u64>()?;
+        let old   = TcpState::from(tail.read::<u32>()?);
+        let new   = TcpState::from(tail.read::<u32>()?);
+        let error = match tail.read::<i32>()? {
+            0     => None,
+            errno => Some(Errno::from(errno)),
+        };
+
+        Ok(State { pid, ts, cgroup, netns, task, src, dst, sk, start, old, new, error })
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if n > self.buf.len() {
+            return Err(anyhow!("need {} bytes, have {}", n, self.buf.len()));
+        }
+
+        let (head, tail) = self.buf.split_at(n);
+        self.buf = tail;
+
+        Ok(head)
+    }
+
+    fn read<T: Pod>(&mut self) -> Result<T> {
+        let mut value = T::zeroed();
+        bytes_of_mut(&mut value).copy_from_slice(self.take(size_of::<T>())?);
+        Ok(value)
+    }
+}
+
+impl Sock4 {
+    fn read(&self) -> Result<(Proto, SocketAddr, SocketAddr)> {
+        let proto = proto(self.proto)?;
+        let saddr = Ipv4Addr::from(self.saddr.to_be());
+        let sport = u16::try_from(self.sport)?;
+        let daddr = Ipv4Addr::from(self.daddr.to_be());
+        let dport = u16::try_from(self.dport)?;
 
         let src = SocketAddr::new(saddr.into(), sport);
         let dst = SocketAddr::new(daddr.into(), dport);
 
-        let mut rx   = 0;
-        let mut tx   = 0;
-        let mut srtt = 0;
-        let mut retx = 0;
+        Ok((proto, src, dst))
+    }
+}
+
+impl Sock6 {
+    fn read(&self) -> Result<(Proto, SocketAddr, SocketAddr)> {
+        let proto = proto(self.proto)?;
+        let saddr = normalize(Ipv6Addr::from(self.saddr));
+        let sport = u16::try_from(self.sport)?;
+        let daddr = normalize(Ipv6Addr::from(self.daddr));
+        let dport = u16::try_from(self.dport)?;
+
+        let src = SocketAddr::new(saddr, sport);
+        let dst = SocketAddr::new(daddr, dport);
+
+        Ok((proto, src, dst))
+    }
+}
+
+impl RawTask {
+    fn read(&self) -> Result<Task> {
+        let comm = cstr(&self.comm);
+        let tid  = self.tid.try_into()?;
+
+        Ok(Task { tid, uid: self.uid, gid: self.gid, comm })
+    }
+}
+
+fn legacy(version: u16) -> Result<usize> {
+    let cgroup = size_of::<u64>();
+    let task   = size_of::<RawTask>();
+    let netns  = size_of::<u64>();
+
+    match version {
+        1 => Ok(0),
+        2 => Ok(cgroup),
+        3 => Ok(cgroup + task),
+        4 => Ok(cgroup + task + netns),
+        _ => Err(anyhow!("missing header size in version {}", version)),
+    }
+}
+
+fn cstr(buf: &[u8]) -> String {
+    let buf = buf.split(|&b| b == 0).next().unwrap_or_default();
+    String::from_utf8_lossy(buf).into_owned()
+}
+
+fn proto(proto: u32) -> Result<Proto> {
+    match proto.try_into()? {
+        IPPROTO_TCP => Ok(Proto::Tcp),
+        IPPROTO_UDP => Ok(Proto::Udp),
+        proto       => Err(anyhow!("invalid proto: {}", proto)),
+    }
+}
+
+pub fn normalize(addr: Ipv6Addr) -> IpAddr {
+    match addr.to_ipv4_mapped() {
+        Some(addr) => addr.into(),
+        None       => addr.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytemuck::bytes_of;
+    use super::*;
+
+    #[test]
+    fn connect6() {
+        let sock = sock(tcp6(CONNECT6, &sock6("2001:db8::1", 40000, "2001:db8::2", 443), &[]));
+
+        assert!(matches!(sock.call, Call::Connect));
+        assert_eq!(sock.pid, 42);
+        assert_eq!(sock.ts, 7);
+        assert_eq!(sock.cgroup, 11);
+        assert_eq!(sock.netns, 12);
+        assert_eq!(sock.proto, Proto::Tcp);
+        assert_eq!(sock.src, addr("[2001:db8::1]:40000"));
+        assert_eq!(sock.dst, addr("[2001:db8::2]:443"));
+        assert_eq!(sock.task.map(|task| (task.tid, task.comm)), Some((43, "curl".to_owned())));
+    }
+
+    #[test]
+    fn accept6_mapped() {
+        let sock = sock(tcp6(ACCEPT6, &sock6("::ffff:10.0.0.1", 80, "::ffff:10.0.0.2", 51000), &[]));
+
+        assert!(matches!(sock.call, Call::Accept));
+        assert_eq!(sock.src, addr("10.0.0.1:80"));
+        assert_eq!(sock.dst, addr("10.0.0.2:51000"));
+    }
+
+    #[test]
+    fn close6() {
+        let mut tail = Vec::new();
+        tail.extend_from_slice(&1500u64.to_ne_bytes());
+        tail.extend_from_slice(&(5u64 << 32).to_ne_bytes());
+        tail.extend_from_slice(&250u32.to_ne_bytes());
+        tail.extend_from_slice(&3u32.to_ne_bytes());
+
+        let sock = sock(tcp6(CLOSE6, &sock6("2001:db8::1", 40000, "2001:db8::2", 443), &tail));
+
+        assert!(matches!(sock.call, Call::Close));
+        assert_eq!((sock.rx, sock.tx), (1500, 5 << 32));
+        assert_eq!((sock.rx_delta, sock.tx_delta), (1500, 5 << 32));
+        assert_eq!((sock.srtt, sock.retx), (250, 3));
+    }
+
+    #[test]
+    fn close6_truncated() {
+        let buf = tcp6(CLOSE6, &sock6("2001:db8::1", 40000, "2001:db8::2", 443), &[0; 8]);
+        assert!(Event::read(&buf).is_err());
+    }
+
+    #[test]
+    fn close_unversioned() {
+        let socket = Sock4 {
+            proto: IPPROTO_TCP as u32,
+            saddr: u32::from(Ipv4Addr::new(10, 0, 0, 1)).to_be(),
+            sport: 40000,
+            daddr: u32::from(Ipv4Addr::new(10, 0, 0, 2)).to_be(),
+            dport: 443,
+        };
+
+        let mut buf = bytes_of(&Unversioned { kind: CLOSE, pid: 42 }).to_vec();
+        buf.extend_from_slice(bytes_of(&socket));
+        for value in &[1500u32, u32::MAX, 250, 3] {
+            buf.extend_from_slice(&value.to_ne_bytes());
+        }
+
+        let sock = match Event::unversioned(&buf).unwrap() {
+            Event::Sock(sock) => sock,
+            event             => panic!("unexpected event: {:?}", event),
+        };
+
+        assert!(matches!(sock.call, Call::Close));
+        assert_eq!(sock.pid, 42);
+        assert_eq!(sock.src, addr("10.0.0.1:40000"));
+        assert_eq!(sock.dst, addr("10.0.0.2:443"));
+        assert_eq!((sock.rx, sock.tx), (1500, u64::from(u32::MAX)));
+        assert_eq!((sock.srtt, sock.retx), (250, 3));
+        assert!(sock.task.is_none());
+
+        assert!(Event::unversioned(&buf[..buf.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn udp_events() {
+        let mut socket = sock6("2001:db8::1", 40000, "2001:db8::53", 53);
+        socket.proto = IPPROTO_UDP as u32;
+
+        let mut data = bytes_of(&socket).to_vec();
+        data.extend_from_slice(&[0; 16]);
+
+        assert!(Event::read(&event(8, &data)).is_err());
+        assert!(Event::read(&event(9, &data)).is_err());
+    }
+
+    #[test]
+    fn udp_flow() {
+        let mut socket = sock6("::ffff:10.0.0.1", 5353, "::ffff:10.0.0.53", 53);
+        socket.proto = IPPROTO_UDP as u32;
 
-        if tail.len() == 16 {
-            rx   = u32::from_ne_bytes(tail[0..4].try_into()?);
-            tx   = u32::from_ne_bytes(tail[4..8].try_into()?);
-            srtt = u32::from_ne_bytes(tail[8..12].try_into()?);
-            retx = u32::from_ne_bytes(tail[12..16].try_into()?);
+        let key  = UdpKey { pid: 42, socket };
+        let flow = UdpFlow {
+            cgroup:  11,
+            netns:   12,
+            ts:      7,
+            rx:      300,
+            tx:      120,
+            rx_pkts: 3,
+            tx_pkts: 2,
+            task:    task(),
+        };
+
+        let sock = Sock::udp(&key, &flow).unwrap();
+
+        assert!(matches!(sock.call, Call::Udp));
+        assert_eq!((sock.pid, sock.ts, sock.cgroup, sock.netns), (42, 7, 11, 12));
+        assert_eq!(sock.src, addr("10.0.0.1:5353"));
+        assert_eq!(sock.dst, addr("10.0.0.53:53"));
+        assert_eq!((sock.rx, sock.tx, sock.rx_pkts, sock.tx_pkts), (300, 120, 3, 2));
+        assert_eq!(sock.task.map(|task| task.comm), Some("curl".to_owned()));
+    }
+
+    #[test]
+    fn legacy_header() {
+        let data = bytes_of(&sock6("2001:db8::1", 40000, "2001:db8::2", 443)).to_vec();
+
+        let mut common = 11u64.to_ne_bytes().to_vec();
+        common.extend_from_slice(bytes_of(&task()));
+
+        let v3 = sock(header(3, CONNECT6, &common, &data));
+        assert_eq!((v3.cgroup, v3.netns), (11, 0));
+        assert_eq!(v3.task.map(|task| task.tid), Some(43));
+
+        common.extend_from_slice(&12u64.to_ne_bytes());
+
+        let v4 = sock(header(4, CONNECT6, &common, &data));
+        assert_eq!((v4.cgroup, v4.netns), (11, 12));
+        assert_eq!(v4.dst, addr("[2001:db8::2]:443"));
+    }
+
+    #[test]
+    fn unknown_common_fields() {
+        let data = bytes_of(&sock6("2001:db8::1", 40000, "2001:db8::2", 443)).to_vec();
+
+        let mut common = 11u64.to_ne_bytes().to_vec();
+        common.extend_from_slice(bytes_of(&task()));
+        common.extend_from_slice(&12u64.to_ne_bytes());
+        common.extend_from_slice(&[0xff; 24]);
+
+        let sock = sock(header(VERSION as u16, CONNECT6, &common, &data));
+
+        assert_eq!((sock.pid, sock.cgroup, sock.netns), (42, 11, 12));
+        assert_eq!(sock.src, addr("[2001:db8::1]:40000"));
+        assert_eq!(sock.dst, addr("[2001:db8::2]:443"));
+    }
+
+    #[test]
+    fn invalid_header() {
+        let data = bytes_of(&sock6("2001:db8::1", 40000, "2001:db8::2", 443)).to_vec();
+
+        let mut buf = header(VERSION as u16, CONNECT6, &[], &data);
+        assert!(Event::read(&buf).is_err());
+
+        buf[2..4].copy_from_slice(&8u16.to_ne_bytes());
+        assert!(Event::read(&buf).is_err());
+
+        buf[..4].copy_from_slice(&[VERSION as u8 + 1, 0, 0, 0]);
+        assert!(Event::read(&buf).is_err());
+    }
+
+    #[test]
+    fn random() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+        for _ in 0..10000 {
+            let len = rng.next() as usize % 512;
+            let buf = (0..len).map(|_| rng.next() as u8).collect::<Vec<_>>();
+            assert!(Event::read(&buf).is_err());
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        for buf in samples() {
+            assert!(Event::read(&buf).is_ok());
+
+            for len in 0..buf.len() {
+                assert!(Event::read(&buf[..len]).is_err(), "{} bytes of {}", len, buf.len());
+            }
+        }
+    }
+
+    #[test]
+    fn corrupted() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+
+        for buf in samples() {
+            for _ in 0..2000 {
+                let mut buf = buf.clone();
+                for _ in 0..1 + rng.next() % 4 {
+                    let index = rng.next() as usize % buf.len();
+                    buf[index] = rng.next() as u8;
+                }
+                let _ = Event::read(&buf);
+            }
         }
+    }
+
+    #[test]
+    fn normalize_addrs() {
+        let v4 = Ipv4Addr::new(192, 168, 1, 1);
+        let v6 = "2001:db8::1".parse::<Ipv6Addr>().unwrap();
+
+        assert_eq!(normalize(v4.to_ipv6_mapped()), IpAddr::V4(v4));
+        assert_eq!(normalize(v6), IpAddr::V6(v6));
+        assert_eq!(normalize(Ipv6Addr::LOCALHOST), IpAddr::V6(Ipv6Addr::LOCALHOST));
+        assert_eq!(normalize(Ipv6Addr::UNSPECIFIED), IpAddr::V6(Ipv6Addr::UNSPECIFIED));
+    }
+
+    fn samples() -> Vec<Vec<u8>> {
+        let socket = sock6("2001:db8::1", 40000, "2001:db8::2", 443);
+
+        let mut state = bytes_of(&socket).to_vec();
+        state.extend_from_slice(&0xffff_8880_0000_0000u64.to_ne_bytes());
+        state.extend_from_slice(&7u64.to_ne_bytes());
+        for value in &[2u32, 1, 0] {
+            state.extend_from_slice(&value.to_ne_bytes());
+        }
+
+        let mut exec = 1u32.to_ne_bytes().to_vec();
+        exec.extend_from_slice(&9u32.to_ne_bytes());
+        exec.extend_from_slice(&[0; FILENAME_SIZE]);
+        exec.extend_from_slice(b"curl\0-v\0\0");
+        exec.resize(8 + FILENAME_SIZE + ARGV_SIZE, 0);
 
-        Ok(Sock { call, pid, src, dst, rx, tx, srtt, retx })
+        vec![
+            tcp6(CONNECT6, &socket, &[]),
+            tcp6(CLOSE6, &socket, &[0; 24]),
+            tcp6(CONNECT6_FAILED, &socket, &(-111i32).to_ne_bytes()),
+            event(STATE6, &state),
+            event(EXEC, &exec),
+            event(FORK, &1u32.to_ne_bytes()),
+        ]
     }
 
+    fn sock(buf: Vec<u8>) -> Sock {
+        match Event::read(&buf).unwrap() {
+            Event::Sock(sock) => sock,
+            event             => panic!("unexpected event: {:?}", event),
+        }
+    }
+
+    fn event(kind: u32, data: &[u8]) -> Vec<u8> {
+        let mut common = 11u64.to_ne_bytes().to_vec();
+        common.extend_from_slice(bytes_of(&task()));
+        common.extend_from_slice(&12u64.to_ne_bytes());
+
+        header(VERSION as u16, kind, &common, data)
+    }
+
+    fn header(version: u16, kind: u32, common: &[u8], data: &[u8]) -> Vec<u8> {
+        let size = match version {
+            1..=4 => 0,
+            _     => size_of::<Header>() + common.len(),
+        };
+
+        let head = Header {
+            version,
+            size:    size as u16,
+            length:  data.len() as u32,
+            kind,
+            pid:     42,
+            ts:      7,
+        };
+
+        let mut buf = bytes_of(&head).to_vec();
+        buf.extend_from_slice(common);
+        buf.extend_from_slice(data);
+        buf
+    }
+
+    fn task() -> RawTask {
+        let mut comm = [0u8; 16];
+        comm[..4].copy_from_slice(b"curl");
+        RawTask { tid: 43, uid: 1000, gid: 1000, pad: 0, comm }
+    }
+
+    fn tcp6(kind: u32, sock: &Sock6, tail: &[u8]) -> Vec<u8> {
+        let mut data = bytes_of(sock).to_vec();
+        data.extend_from_slice(tail);
+        event(kind, &data)
+    }
+
+    fn sock6(src: &str, sport: u16, dst: &str, dport: u16) -> Sock6 {
+        let saddr = src.parse::<Ipv6Addr>().unwrap().octets();
+        let daddr = dst.parse::<Ipv6Addr>().unwrap().octets();
+        let proto = IPPROTO_TCP as u32;
+        Sock6 { proto, saddr, sport: sport.into(), daddr, dport: dport.into() }
+    }
+
+    fn addr(addr: &str) -> SocketAddr {
+        addr.parse().unwrap()
+    }
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
 }
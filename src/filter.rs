@@ -0,0 +1,309 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use anyhow::{anyhow, Error, Result};
+
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    pub allow:    Vec<u16>,
+    pub deny:     Vec<u16>,
+    pub include:  Vec<Cidr>,
+    pub exclude:  Vec<Cidr>,
+    pub loopback: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cidr {
+    pub addr: IpAddr,
+    pub len:  u8,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Rules {
+    pub flags:    u32,
+    pub prefixes: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Prefix {
+    pub addr:   [u32; 4],
+    pub mask:   [u32; 4],
+    pub len:    u32,
+    pub action: u32,
+}
+
+pub trait Table<K, V> {
+    fn set(&mut self, key: K, value: V) -> Result<()>;
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        let Self { allow, deny, include, exclude, loopback } = self;
+        allow.is_empty() && deny.is_empty() && include.is_empty() && exclude.is_empty() && !loopback
+    }
+
+    pub fn populate<R, P, X>(&self, rules: &mut R, ports: &mut P, prefixes: &mut X) -> Result<()>
+    where
+        R: Table<u32, Rules>,
+        P: Table<u32, u32>,
+        X: Table<u32, Prefix>,
+    {
+        if let Some(port) = self.allow.iter().find(|port| self.deny.contains(port)) {
+            return Err(anyhow!("port {} is both allowed and denied", port));
+        }
+
+        if let Some(cidr) = self.include.iter().find(|cidr| {
+            self.exclude.iter().any(|other| cidr.prefix(0) == other.prefix(0))
+        }) {
+            return Err(anyhow!("{} is both included and excluded", cidr));
+        }
+
+        let count = self.allow.len() + self.deny.len();
+        if count > PORTS {
+            return Err(anyhow!("{} ports exceed the limit of {}", count, PORTS));
+        }
+
+        let count = self.include.len() + self.exclude.len();
+        if count > PREFIXES {
+            return Err(anyhow!("{} CIDRs exceed the limit of {}", count, PREFIXES));
+        }
+
+        for &port in &self.allow {
+            ports.set(port.into(), PORT_ALLOW)?;
+        }
+
+        for &port in &self.deny {
+            ports.set(port.into(), PORT_DENY)?;
+        }
+
+        let include = self.include.iter().map(|cidr| cidr.prefix(PREFIX_INCLUDE));
+        let exclude = self.exclude.iter().map(|cidr| cidr.prefix(PREFIX_EXCLUDE));
+
+        for (index, prefix) in include.chain(exclude).enumerate() {
+            prefixes.set(u32::try_from(index)?, prefix)?;
+        }
+
+        let flags = [
+            (!self.allow.is_empty(),   FILTER_ALLOW_PORTS),
+            (!self.deny.is_empty(),    FILTER_DENY_PORTS),
+            (!self.include.is_empty(), FILTER_INCLUDE),
+            (!self.exclude.is_empty(), FILTER_EXCLUDE),
+            (self.loopback,            FILTER_LOOPBACK),
+        ].iter().filter(|(set, _)| *set).fold(0, |flags, (_, flag)| flags | flag);
+
+        let prefixes = u32::try_from(count)?;
+
+        rules.set(0, Rules { flags, prefixes })
+    }
+}
+
+impl Cidr {
+    pub fn prefix(&self, action: u32) -> Prefix {
+        let (addr, len) = match self.addr {
+            IpAddr::V4(addr) => (addr.to_ipv6_mapped(), u32::from(self.len) + 96),
+            IpAddr::V6(addr) => (addr, u32::from(self.len)),
+        };
+
+        let mask = u128::MAX.checked_shl(128 - len).unwrap_or(0);
+        let addr = u128::from(addr) & mask;
+
+        Prefix {
+            addr: words(addr),
+            mask: words(mask),
+            len,
+            action,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = Error;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow!("invalid CIDR: {}", arg);
+
+        let (addr, len) = match arg.split_once('/') {
+            Some((addr, len)) => (addr, Some(len)),
+            None              => (arg, None),
+        };
+
+        let addr = addr.parse::<IpAddr>().map_err(|_| invalid())?;
+        let max  = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        let len = match len {
+            Some(len) => len.parse::<u8>().ok().filter(|&len| len <= max).ok_or_else(invalid)?,
+            None      => max,
+        };
+
+        Ok(Self { addr, len })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.len)
+    }
+}
+
+fn words(value: u128) -> [u32; 4] {
+    let bytes = value.to_be_bytes();
+    let mut words = [0; 4];
+    for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+        *word = u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    words
+}
+
+pub const PORTS:    usize = 1024;
+pub const PREFIXES: usize = 16;
+
+const FILTER_ALLOW_PORTS: u32 = 1 << 0;
+const FILTER_DENY_PORTS:  u32 = 1 << 1;
+const FILTER_INCLUDE:     u32 = 1 << 2;
+const FILTER_EXCLUDE:     u32 = 1 << 3;
+const FILTER_LOOPBACK:    u32 = 1 << 4;
+
+const PORT_ALLOW:     u32 = 1;
+const PORT_DENY:      u32 = 2;
+const PREFIX_INCLUDE: u32 = 1;
+const PREFIX_EXCLUDE: u32 = 2;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use super::*;
+
+    #[derive(Default)]
+    struct Mock<V> {
+        entries: BTreeMap<u32, V>,
+    }
+
+    impl<V> Table<u32, V> for Mock<V> {
+        fn set(&mut self, key: u32, value: V) -> Result<()> {
+            self.entries.insert(key, value);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn flags() {
+        let (rules, _, _) = populate(&Filter::default()).unwrap();
+        assert_eq!(rules.entries[&0], Rules { flags: 0, prefixes: 0 });
+
+        let filter = Filter { allow: vec![443], loopback: true, ..Default::default() };
+        let (rules, _, _) = populate(&filter).unwrap();
+        assert_eq!(rules.entries[&0].flags, FILTER_ALLOW_PORTS | FILTER_LOOPBACK);
+
+        let filter = Filter {
+            allow:    vec![443],
+            deny:     vec![22],
+            include:  vec![cidr("10.0.0.0/8")],
+            exclude:  vec![cidr("10.1.0.0/16")],
+            loopback: true,
+        };
+        let (rules, _, _) = populate(&filter).unwrap();
+        assert_eq!(rules.entries[&0], Rules { flags: 31, prefixes: 2 });
+    }
+
+    #[test]
+    fn ports() {
+        let filter = Filter { allow: vec![80, 443], deny: vec![22], ..Default::default() };
+        let (_, ports, _) = populate(&filter).unwrap();
+
+        let expected = vec![(22, PORT_DENY), (80, PORT_ALLOW), (443, PORT_ALLOW)];
+        assert_eq!(ports.entries.into_iter().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn prefixes() {
+        let filter = Filter {
+            include: vec![cidr("10.1.2.3/8"), cidr("2001:db8::/32")],
+            exclude: vec![cidr("192.168.1.0/24")],
+            ..Default::default()
+        };
+        let (_, _, prefixes) = populate(&filter).unwrap();
+
+        assert_eq!(prefixes.entries[&0], Prefix {
+            addr:   [0, 0, word([0, 0, 0xff, 0xff]), word([10, 0, 0, 0])],
+            mask:   [!0, !0, !0, word([0xff, 0, 0, 0])],
+            len:    104,
+            action: PREFIX_INCLUDE,
+        });
+
+        assert_eq!(prefixes.entries[&1], Prefix {
+            addr:   [word([0x20, 0x01, 0x0d, 0xb8]), 0, 0, 0],
+            mask:   [!0, 0, 0, 0],
+            len:    32,
+            action: PREFIX_INCLUDE,
+        });
+
+        assert_eq!(prefixes.entries[&2], Prefix {
+            addr:   [0, 0, word([0, 0, 0xff, 0xff]), word([192, 168, 1, 0])],
+            mask:   [!0, !0, !0, word([0xff, 0xff, 0xff, 0])],
+            len:    120,
+            action: PREFIX_EXCLUDE,
+        });
+
+        assert_eq!(cidr("::/0").prefix(PREFIX_EXCLUDE).mask, [0; 4]);
+        assert_eq!(cidr("::1").prefix(PREFIX_EXCLUDE).mask, [!0; 4]);
+    }
+
+    #[test]
+    fn conflicts() {
+        let filter = Filter { allow: vec![22, 80], deny: vec![80], ..Default::default() };
+        assert_eq!(error(&filter), "port 80 is both allowed and denied");
+
+        let filter = Filter {
+            include: vec![cidr("10.0.0.1/8")],
+            exclude: vec![cidr("10.0.0.0/8")],
+            ..Default::default()
+        };
+        assert_eq!(error(&filter), "10.0.0.1/8 is both included and excluded");
+    }
+
+    #[test]
+    fn limits() {
+        let filter = Filter { allow: (0..=1024).collect(), ..Default::default() };
+        assert_eq!(error(&filter), "1025 ports exceed the limit of 1024");
+
+        let filter = Filter { include: vec![cidr("10.0.0.0/8"); 17], ..Default::default() };
+        assert_eq!(error(&filter), "17 CIDRs exceed the limit of 16");
+
+        let filter = Filter { allow: (0..1024).collect(), include: vec![cidr("10.0.0.0/8"); 16], ..Default::default() };
+        assert!(populate(&filter).is_ok());
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(cidr("10.0.0.0/8"), Cidr { addr: "10.0.0.0".parse().unwrap(), len: 8 });
+        assert_eq!(cidr("::1").len, 128);
+        assert_eq!(cidr("1.2.3.4").to_string(), "1.2.3.4/32");
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("::/129".parse::<Cidr>().is_err());
+        assert!("host/8".parse::<Cidr>().is_err());
+    }
+
+    fn populate(filter: &Filter) -> Result<(Mock<Rules>, Mock<u32>, Mock<Prefix>)> {
+        let (mut rules, mut ports, mut prefixes) = (Mock::default(), Mock::default(), Mock::default());
+        filter.populate(&mut rules, &mut ports, &mut prefixes)?;
+        Ok((rules, ports, prefixes))
+    }
+
+    fn error(filter: &Filter) -> String {
+        populate(filter).err().map(|e| e.to_string()).unwrap_or_default()
+    }
+
+    fn cidr(cidr: &str) -> Cidr {
+        cidr.parse().unwrap()
+    }
+
+    fn word(bytes: [u8; 4]) -> u32 {
+        u32::from_ne_bytes(bytes)
+    }
+}
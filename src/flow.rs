@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use libc::pid_t;
+use log::{debug, error};
+use tokio::select;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::time::interval;
+use crate::data::Direction;
+use crate::event::{Call, Sock, Span};
+
+type Key = (pid_t, SocketAddr, SocketAddr);
+
+pub fn assemble(rx: Receiver<Sock>, timeout: Duration) -> Receiver<Sock> {
+    let (tx, rx1) = channel(1024);
+
+    tokio::spawn(async move {
+        match exec(rx, tx, timeout).await {
+            Ok(()) => debug!("assembler finished"),
+            Err(e) => error!("assembler failed: {:?}", e),
+        }
+    });
+
+    rx1
+}
+
+async fn exec(mut rx: Receiver<Sock>, tx: Sender<Sock>, timeout: Duration) -> Result<()> {
+    let mut opens    = HashMap::<Key, (Sock, Instant)>::new();
+    let mut interval = interval(timeout / 4);
+
+    loop {
+        select! {
+            sock = rx.recv() => match sock {
+                Some(sock) => match sock.call {
+                    Call::Connect | Call::Accept => {
+                        opens.insert((sock.pid, sock.src, sock.dst), (sock, Instant::now()));
+                    }
+                    Call::Close => match opens.remove(&(sock.pid, sock.src, sock.dst)) {
+                        Some((open, _)) => tx.send(flow(open, sock)).await?,
+                        None            => tx.send(sock).await?,
+                    },
+                    _ => tx.send(sock).await?,
+                },
+                None => break,
+            },
+            _ = interval.tick() => {
+                let expired = opens.iter().filter(|(_, (_, opened))| {
+                    opened.elapsed() > timeout
+                }).map(|(key, _)| *key).collect::<Vec<_>>();
+
+                debug!("expiring {} open flows", expired.len());
+
+                for key in expired {
+                    if let Some((open, _)) = opens.remove(&key) {
+                        tx.send(open).await?;
+                    }
+                }
+            }
+        }
+    }
+
+    for (_, (open, _)) in opens.drain() {
+        tx.send(open).await?;
+    }
+
+    Ok(())
+}
+
+fn flow(open: Sock, close: Sock) -> Sock {
+    let direction = match open.call {
+        Call::Accept => Direction::Server,
+        _            => Direction::Client,
+    };
+
+    Sock {
+        call:              Call::Flow,
+        pid:               open.pid,
+        cgroup:            open.cgroup,
+        task:              open.task,
+        handshake_latency: open.handshake_latency,
+        flow:              Some(Span {
+            start: open.ts,
+            direction,
+        }),
+        ..close
+    }
+}
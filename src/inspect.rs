@@ -0,0 +1,306 @@
+use std::convert::TryInto;
+use std::fmt;
+use std::mem::size_of;
+use anyhow::{anyhow, Result};
+use crate::attach::{self, Hook};
+use crate::elf::Elf;
+use crate::event::{Live, UdpFlow, UdpKey, KINDS, VERSION};
+use crate::filter::{Prefix, Rules};
+
+pub struct Bytecode {
+    pub core:     bool,
+    pub kernel:   Option<u32>,
+    pub meta:     Option<Meta>,
+    pub maps:     Vec<Map>,
+    pub programs: Vec<Program>,
+    pub issues:   Vec<Issue>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Meta {
+    pub version: u32,
+    pub kinds:   u32,
+}
+
+#[derive(Debug)]
+pub struct Map {
+    pub name:    String,
+    pub kind:    u32,
+    pub key:     u32,
+    pub value:   u32,
+    pub entries: u32,
+}
+
+#[derive(Debug)]
+pub struct Program {
+    pub kind: String,
+    pub name: String,
+}
+
+#[derive(Debug)]
+pub enum Issue {
+    Error(String),
+    Warning(String),
+}
+
+impl Bytecode {
+    pub fn inspect(bytecode: &[u8]) -> Result<Self> {
+        let elf = Elf::parse(bytecode)?;
+
+        let kernel = elf.section("version").map(|section| {
+            field(section.data, 0)
+        }).transpose()?;
+
+        let meta = elf.section("meta").map(|section| -> Result<_> {
+            Ok(Meta {
+                version: field(section.data, 0)?,
+                kinds:   field(section.data, 1)?,
+            })
+        }).transpose()?;
+
+        let maps = elf.sections().filter_map(|section| {
+            section.name.strip_prefix("maps/").map(|name| (name, section.data))
+        }).map(|(name, data)| -> Result<_> {
+            Ok(Map {
+                name:    name.to_owned(),
+                kind:    field(data, 0)?,
+                key:     field(data, 1)?,
+                value:   field(data, 2)?,
+                entries: field(data, 3)?,
+            })
+        }).collect::<Result<Vec<_>>>()?;
+
+        let programs = elf.sections().filter(|section| {
+            section.kind == SHT_PROGBITS && !section.name.starts_with('.')
+        }).filter_map(|section| {
+            let (kind, name) = section.name.split_once('/')?;
+            Some(Program {
+                kind: kind.to_owned(),
+                name: name.to_owned(),
+            }).filter(|_| kind != "maps")
+        }).collect::<Vec<_>>();
+
+        let mut issues = Vec::new();
+
+        if elf.machine != EM_BPF {
+            issues.push(Issue::Error(format!("not a BPF object (machine {})", elf.machine)));
+        }
+
+        if elf.section("license").is_none() {
+            issues.push(Issue::Error("missing license section".to_owned()));
+        }
+
+        match meta {
+            Some(meta) if meta.version == 0 || meta.version > VERSION => {
+                let error = format!("event version {} is not supported, expected at most {}", meta.version, VERSION);
+                issues.push(Issue::Error(error));
+            }
+            Some(meta) if meta.kinds > KINDS => {
+                let error = format!("bytecode emits {} event kinds, only {} are understood", meta.kinds, KINDS);
+                issues.push(Issue::Error(error));
+            }
+            Some(_) => (),
+            None    => issues.push(Issue::Warning("missing meta section, decoding unversioned events".to_owned())),
+        }
+
+        check_maps(&maps, &mut issues);
+        check_programs(&programs, &mut issues);
+
+        let core = kernel.is_none();
+
+        Ok(Self { core, kernel, meta, maps, programs, issues })
+    }
+
+    pub fn check(&self) -> Result<()> {
+        let errors = self.errors().collect::<Vec<_>>();
+
+        if !errors.is_empty() {
+            return Err(anyhow!("incompatible bytecode: {}", errors.join("; ")));
+        }
+
+        Ok(())
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &str> {
+        self.issues.iter().filter_map(|issue| match issue {
+            Issue::Error(error) => Some(error.as_str()),
+            Issue::Warning(_)   => None,
+        })
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &str> {
+        self.issues.iter().filter_map(|issue| match issue {
+            Issue::Error(_)       => None,
+            Issue::Warning(error) => Some(error.as_str()),
+        })
+    }
+}
+
+fn check_maps(maps: &[Map], issues: &mut Vec<Issue>) {
+    let map = |name: &str| maps.iter().find(|map| map.name == name);
+
+    match map("events") {
+        Some(events) if events.kind == BPF_MAP_TYPE_RINGBUF => {
+            let lost = map("lost").filter(|lost| {
+                lost.kind == BPF_MAP_TYPE_PERCPU_ARRAY && lost.value == 8
+            });
+
+            if lost.is_none() {
+                issues.push(Issue::Error("missing map lost, a per-cpu u64 array required with ringbuf events".to_owned()));
+            }
+        }
+        Some(events) if events.kind == BPF_MAP_TYPE_PERF_EVENT_ARRAY => (),
+        Some(events) => {
+            issues.push(Issue::Error(format!("map events has unsupported type {}", map_type(events.kind))));
+        }
+        None => {
+            issues.push(Issue::Error("missing map events".to_owned()));
+        }
+    }
+
+    let size = size_of::<Live>();
+
+    match map("live") {
+        Some(live) if live.key == 8 && live.value as usize == size => (),
+        Some(live) => {
+            let error = format!("map live has key size {} and value size {}, expected 8 and {}", live.key, live.value, size);
+            issues.push(Issue::Error(error));
+        }
+        None => {
+            issues.push(Issue::Warning("missing map live, --update-interval unavailable".to_owned()));
+        }
+    }
+
+    let (key, value) = (size_of::<UdpKey>(), size_of::<UdpFlow>());
+
+    match map("udp") {
+        Some(udp) if udp.kind == BPF_MAP_TYPE_HASH && udp.key as usize == key && udp.value as usize == value => (),
+        Some(udp) => {
+            let error = format!("map udp is a {} with key size {} and value size {}, expected {} with {} and {}", map_type(udp.kind), udp.key, udp.value, map_type(BPF_MAP_TYPE_HASH), key, value);
+            issues.push(Issue::Error(error));
+        }
+        None => {
+            issues.push(Issue::Warning("missing map udp, UDP traffic unavailable".to_owned()));
+        }
+    }
+
+    let filters = [
+        ("rules",    BPF_MAP_TYPE_ARRAY, size_of::<Rules>()),
+        ("ports",    BPF_MAP_TYPE_HASH,  size_of::<u32>()),
+        ("prefixes", BPF_MAP_TYPE_ARRAY, size_of::<Prefix>()),
+    ];
+
+    for &(name, kind, size) in &filters {
+        match map(name) {
+            Some(found) if found.kind == kind && found.key == 4 && found.value as usize == size => (),
+            Some(found) => {
+                let error = format!("map {} is a {} with key size {} and value size {}, expected {} with 4 and {}", name, map_type(found.kind), found.key, found.value, map_type(kind), size);
+                issues.push(Issue::Error(error));
+            }
+            None => {
+                issues.push(Issue::Warning(format!("missing map {}, in-kernel filtering unavailable", name)));
+            }
+        }
+    }
+}
+
+fn check_programs(programs: &[Program], issues: &mut Vec<Issue>) {
+    for program in programs {
+        let expected = match attach::target(&program.name).map(|target| &target.hook) {
+            Some(Hook::KProbe(_))        => &["kprobe", "kretprobe"][..],
+            Some(Hook::TracePoint(_, _)) => &["tracepoint"][..],
+            None                         => {
+                issues.push(Issue::Error(format!("unexpected program {}/{}", program.kind, program.name)));
+                continue;
+            }
+        };
+
+        if !expected.contains(&program.kind.as_str()) {
+            let error = format!("program {} is a {}, expected {}", program.name, program.kind, expected.join(" or "));
+            issues.push(Issue::Error(error));
+        }
+    }
+
+    for target in attach::TARGETS {
+        if programs.iter().any(|program| program.name == target.program) {
+            continue;
+        }
+
+        issues.push(match target.required {
+            true  => Issue::Error(format!("missing required program {}", target.program)),
+            false => Issue::Warning(format!("missing optional program {}", target.program)),
+        });
+    }
+}
+
+impl fmt::Display for Bytecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kernel {
+            Some(v) => writeln!(f, "object:   built for kernel {}.{}.{}", v >> 16, (v >> 8) & 0xff, v & 0xff)?,
+            None    => writeln!(f, "object:   CO-RE, requires BTF")?,
+        }
+
+        match self.meta {
+            Some(meta) => writeln!(f, "events:   version {}, {} kinds (supported: version {}, {} kinds)", meta.version, meta.kinds, VERSION, KINDS)?,
+            None       => writeln!(f, "events:   unversioned (supported: version {}, {} kinds)", VERSION, KINDS)?,
+        }
+
+        writeln!(f, "maps:")?;
+        for map in &self.maps {
+            let kind = map_type(map.kind);
+            writeln!(f, "  {:<8} {:<16} key {:<2} value {:<4} entries {}", map.name, kind, map.key, map.value, map.entries)?;
+        }
+
+        writeln!(f, "programs:")?;
+        for program in &self.programs {
+            writeln!(f, "  {}/{}", program.kind, program.name)?;
+        }
+
+        for issue in &self.issues {
+            writeln!(f, "{}", issue)?;
+        }
+
+        match self.errors().next() {
+            Some(_) => writeln!(f, "result:   incompatible"),
+            None    => writeln!(f, "result:   compatible"),
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error(error)     => write!(f, "error:    {}", error),
+            Self::Warning(warning) => write!(f, "warning:  {}", warning),
+        }
+    }
+}
+
+fn map_type(kind: u32) -> String {
+    match kind {
+        BPF_MAP_TYPE_HASH             => "hash".to_owned(),
+        BPF_MAP_TYPE_ARRAY            => "array".to_owned(),
+        BPF_MAP_TYPE_PERF_EVENT_ARRAY => "perf_event_array".to_owned(),
+        BPF_MAP_TYPE_PERCPU_ARRAY     => "percpu_array".to_owned(),
+        BPF_MAP_TYPE_LRU_HASH         => "lru_hash".to_owned(),
+        BPF_MAP_TYPE_RINGBUF          => "ringbuf".to_owned(),
+        kind                          => format!("type {}", kind),
+    }
+}
+
+fn field(data: &[u8], index: usize) -> Result<u32> {
+    let bytes = data.get(index * 4..index * 4 + 4).ok_or_else(|| {
+        anyhow!("truncated section")
+    })?;
+    Ok(u32::from_le_bytes(bytes.try_into()?))
+}
+
+const EM_BPF:       u16 = 247;
+const SHT_PROGBITS: u32 = 1;
+
+const BPF_MAP_TYPE_HASH:             u32 = 1;
+const BPF_MAP_TYPE_ARRAY:            u32 = 2;
+const BPF_MAP_TYPE_PERF_EVENT_ARRAY: u32 = 4;
+const BPF_MAP_TYPE_PERCPU_ARRAY:     u32 = 6;
+const BPF_MAP_TYPE_LRU_HASH:         u32 = 9;
+const BPF_MAP_TYPE_RINGBUF:          u32 = 27;
@@ -1,5 +1,18 @@
+pub mod attach;
+pub mod clock;
 pub mod code;
 pub mod data;
+pub mod elf;
 pub mod event;
+pub mod filter;
+pub mod flow;
+pub mod inspect;
+pub mod live;
+pub mod queue;
+pub mod ring;
 pub mod sink;
+pub mod snapshot;
+pub mod state;
+pub mod stats;
 pub mod track;
+pub mod udp;
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::time::Duration;
+use anyhow::Result;
+use log::{debug, error};
+use tokio::select;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::time::interval;
+use crate::clock::monotonic;
+use crate::code::LiveMap;
+use crate::event::{Call, Sock};
+
+type Key = (u64, SocketAddr, SocketAddr);
+
+pub fn poll(rx: Receiver<Sock>, map: LiveMap, period: Duration) -> Receiver<Sock> {
+    let (tx, rx1) = channel(1024);
+
+    tokio::spawn(async move {
+        match exec(rx, tx, map, period).await {
+            Ok(()) => debug!("poller finished"),
+            Err(e) => error!("poller failed: {:?}", e),
+        }
+    });
+
+    rx1
+}
+
+async fn exec(mut rx: Receiver<Sock>, tx: Sender<Sock>, map: LiveMap, period: Duration) -> Result<()> {
+    let mut last     = HashMap::<Key, (u64, u64, u64)>::new();
+    let mut interval = interval(period);
+    let mut tick     = 0;
+
+    loop {
+        select! {
+            sock = rx.recv() => match sock {
+                Some(mut sock) => {
+                    if let Call::Close = sock.call {
+                        if let Some((rx0, tx0, _)) = last.remove(&(sock.netns, sock.src, sock.dst)) {
+                            sock.rx_delta = sock.rx.saturating_sub(rx0);
+                            sock.tx_delta = sock.tx.saturating_sub(tx0);
+                        }
+                    }
+                    tx.send(sock).await?;
+                }
+                None => break,
+            },
+            _ = interval.tick() => {
+                tick += 1;
+
+                let ts    = u64::try_from(monotonic()?.as_nanos())?;
+                let socks = unsafe { map.iter() }.map(|entry| {
+                    let (_, live) = entry?;
+                    Sock::live(&live, ts)
+                }).collect::<Result<Vec<_>>>()?;
+
+                debug!("updating {} live sockets", socks.len());
+
+                for mut sock in socks {
+                    let key = (sock.netns, sock.src, sock.dst);
+
+                    if let Some((rx0, tx0, _)) = last.get(&key) {
+                        sock.rx_delta = sock.rx.saturating_sub(*rx0);
+                        sock.tx_delta = sock.tx.saturating_sub(*tx0);
+                    }
+                    last.insert(key, (sock.rx, sock.tx, tick));
+
+                    tx.send(sock).await?;
+                }
+
+                last.retain(|_, (_, _, seen)| *seen + 1 >= tick);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,17 +1,30 @@
 use std::fs;
 use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use std::time::Duration;
 use std::process::exit;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use env_logger::Builder;
 use gumdrop::Options;
-use log::{trace, LevelFilter};
+use log::{debug, info, trace, warn, LevelFilter};
 use tokio::select;
 use tokio::signal::unix::{signal, SignalKind};
-use convis::code::Code;
-use convis::data::Record;
+use tokio::sync::oneshot;
+use tokio::time::{sleep_until, timeout, Instant};
+use convis::attach::Kernel;
+use convis::clock::Clock;
+use convis::code::{self, Code, Config};
+use convis::data::{Flow, Record};
+use convis::filter::{Cidr, Filter};
 use convis::sink::Sink;
+use convis::flow;
+use convis::inspect::Bytecode;
+use convis::live;
+use convis::queue::Policy;
+use convis::ring;
+use convis::state;
+use convis::stats::Stats;
 use convis::track::Tracker;
+use convis::udp;
 
 #[derive(Options)]
 pub struct Args {
@@ -20,9 +33,56 @@ pub struct Args {
     #[options()]
     bytecode: Option<String>,
     #[options()]
+    btf: Option<String>,
+    #[options()]
     sink: Option<Sink>,
+    #[options()]
+    flows: bool,
+    #[options(default = "300")]
+    flow_timeout: u64,
+    #[options()]
+    update_interval: Option<u64>,
+    #[options(default = "0")]
+    ancestry: usize,
+    #[options(default = "1024")]
+    channel_capacity: usize,
+    #[options()]
+    perf_pages: Option<usize>,
+    #[options(default = "10")]
+    perf_buffers: usize,
+    #[options(default = "1024")]
+    perf_buffer_size: usize,
+    #[options(default = "block")]
+    backpressure: Policy,
+    #[options(default = "10")]
+    drain_timeout: u64,
+    #[options()]
+    allow_port: Vec<u16>,
+    #[options()]
+    deny_port: Vec<u16>,
+    #[options()]
+    include_cidr: Vec<Cidr>,
+    #[options()]
+    exclude_cidr: Vec<Cidr>,
+    #[options()]
+    drop_loopback: bool,
     #[options(count)]
     verbose: u32,
+    #[options(command)]
+    command: Option<Command>,
+}
+
+#[derive(Options)]
+pub enum Command {
+    InspectBytecode(InspectArgs),
+}
+
+#[derive(Options)]
+pub struct InspectArgs {
+    #[options()]
+    help: bool,
+    #[options(free)]
+    file: Option<String>,
 }
 
 #[tokio::main]
@@ -38,54 +98,240 @@ async fn main() -> Result<()> {
     });
     builder.init();
 
-    let mut code = Code::load(&match args.bytecode {
+    if let Some(Command::InspectBytecode(inspect)) = args.command {
+        let file     = inspect.file.ok_or_else(|| anyhow!("missing bytecode file"))?;
+        let bytecode = Bytecode::inspect(&fs::read(file)?)?;
+        print!("{}", bytecode);
+        return bytecode.check();
+    }
+
+    if args.flow_timeout == 0 {
+        return Err(anyhow!("--flow-timeout must be greater than 0"));
+    }
+
+    if args.update_interval == Some(0) {
+        return Err(anyhow!("--update-interval must be greater than 0"));
+    }
+
+    if args.channel_capacity == 0 {
+        return Err(anyhow!("--channel-capacity must be greater than 0"));
+    }
+
+    if args.perf_buffers == 0 {
+        return Err(anyhow!("--perf-buffers must be greater than 0"));
+    }
+
+    if let Some(pages) = args.perf_pages.filter(|pages| !pages.is_power_of_two()) {
+        return Err(anyhow!("--perf-pages must be a power of two, not {}", pages));
+    }
+
+    let btf = code::btf(args.btf.as_deref())?;
+
+    if args.bytecode.is_none() && btf.is_some() && CORE.is_none() {
+        warn!("kernel BTF found but no CO-RE bytecode is embedded, build with --features core");
+    }
+
+    let bytecode = match args.bytecode {
         Some(file) => fs::read(file)?,
-        None       => BYTECODE.to_vec(),
-    })?;
+        None       => embedded(btf.is_some(), ring::supported()).to_vec(),
+    };
+
+    let mut code = Code::load(&bytecode, btf.as_ref())?;
+
+    info!("using {} event transport", code.transport());
+
+    let filter = Filter {
+        allow:    args.allow_port,
+        deny:     args.deny_port,
+        include:  args.include_cidr,
+        exclude:  args.exclude_cidr,
+        loopback: args.drop_loopback,
+    };
+
+    code.filter(&filter)?;
+
+    let (stop, mut shutdown) = oneshot::channel();
 
     let mut sigint  = signal(SignalKind::interrupt())?;
     let mut sigterm = signal(SignalKind::terminate())?;
     tokio::spawn(async move {
         select! {
-            _ = sigint.recv()  => exit(0),
-            _ = sigterm.recv() => exit(0),
+            _ = sigint.recv()  => stop.send(()).ok(),
+            _ = sigterm.recv() => stop.send(()).ok(),
+        };
+        select! {
+            _ = sigint.recv()  => exit(1),
+            _ = sigterm.recv() => exit(1),
         }
     });
 
     let hostname = Arc::new(hostname::get()?.to_string_lossy().to_string());
     let tracker  = Arc::new(Tracker::new().await?);
+    let clock    = Arc::new(Clock::new()?);
+    let stats    = Arc::new(Stats::new(hostname.clone()));
+
+    sink.attach(stats.clone());
+
+    clock.clone().spawn(Duration::from_secs(60));
+
+    let config = Config {
+        capacity: args.channel_capacity,
+        pages:    args.perf_pages,
+        buffers:  args.perf_buffers,
+        buffer:   args.perf_buffer_size,
+        policy:   args.backpressure,
+    };
+
+    let (execs, socks, states) = code.exec(&config, stats.clone())?;
+
+    let report = code.attach(&Kernel::probe())?;
+
+    for attached in &report.attached {
+        debug!("attached {} to {}", attached.program, attached.target);
+    }
+
+    for skipped in &report.skipped {
+        warn!("skipped {}: {}", skipped.program, skipped.reason);
+    }
+
+    info!("attached {} programs, skipped {}", report.attached.len(), report.skipped.len());
+
+    stats.programs(&report);
+    report.check()?;
 
-    let (execs, mut socks) = code.exec()?;
     tracker.clone().spawn(execs);
 
-    while let Some(event) = socks.recv().await {
-        let timestamp = SystemTime::now();
+    let socks = state::track(socks, states, Tracker::sockets(), Duration::from_secs(10));
+
+    let socks = match args.update_interval {
+        Some(secs) => live::poll(socks, code.live()?, Duration::from_secs(secs)),
+        None       => socks,
+    };
+
+    let mut socks = udp::aggregate(socks, code.udp()?, Duration::from_secs(10));
+
+    if args.flows {
+        socks = flow::assemble(socks, Duration::from_secs(args.flow_timeout));
+    }
+
+    let drain        = Duration::from_secs(args.drain_timeout);
+    let mut deadline = None;
+
+    loop {
+        let event = select! {
+            event = socks.recv() => match event {
+                Some(event) => event,
+                None        => break,
+            },
+            _ = &mut shutdown, if deadline.is_none() => {
+                info!("shutting down, draining events");
+                code.stop();
+                deadline = Some(Instant::now() + drain);
+                continue;
+            }
+            _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                warn!("timed out draining events");
+                break;
+            }
+        };
+
+        let timestamp = clock.time(event.ts);
 
         trace!("{:?}", event);
 
-        if let Some(process) = tracker.get(event.pid).await {
-            let record = Record {
-                timestamp: timestamp,
-                event:     format!("{:?}", event.call),
-                src:       event.src,
-                dst:       event.dst,
-                process:   process,
-                hostname:  hostname.clone(),
-                rx:        event.rx,
-                tx:        event.tx,
-                srtt:      Duration::from_micros(event.srtt.into()),
-                retx:      event.retx,
-            };
-            trace!("{:?}", record);
-            sink.send(record)?;
+        let task    = event.task.as_ref();
+        let process  = tracker.get(event.pid, event.cgroup, task).await;
+        let ancestry = tracker.ancestry(&process, args.ancestry);
+        let pod      = process.pod.clone().or_else(|| tracker.pod(event.netns));
+
+        let record = Record {
+            timestamp:         timestamp,
+            event:             format!("{:?}", event.call),
+            proto:             event.proto,
+            src:               event.src,
+            dst:               event.dst,
+            process:           process,
+            ancestry:          ancestry,
+            netns:             event.netns,
+            pod:               pod,
+            tid:               task.map(|t| t.tid),
+            hostname:          hostname.clone(),
+            rx:                event.rx,
+            tx:                event.tx,
+            rx_delta:          event.rx_delta,
+            tx_delta:          event.tx_delta,
+            rx_pkts:           event.rx_pkts,
+            tx_pkts:           event.tx_pkts,
+            srtt:              Duration::from_micros(event.srtt.into()),
+            retx:              event.retx,
+            error:             event.error,
+            handshake_latency: event.handshake_latency,
+            buildup:           event.buildup,
+            flow:              event.flow.map(|span| {
+                let start = clock.time(span.start);
+                Flow {
+                    start:     start,
+                    end:       timestamp,
+                    duration:  timestamp.duration_since(start).unwrap_or_default(),
+                    direction: span.direction,
+                }
+            }),
         };
+        trace!("{:?}", record);
+        sink.send(record)?;
+    }
+
+    code.stop();
+
+    match timeout(drain, sink.flush()).await {
+        Ok(result) => result?,
+        Err(_)     => warn!("timed out flushing sink"),
     }
 
     Ok(())
 }
 
+fn embedded(core: bool, ringbuf: bool) -> &'static [u8] {
+    let objects = [
+        (core && ringbuf, CORE_RINGBUF),
+        (core,            CORE),
+        (ringbuf,         RINGBUF),
+    ];
+
+    objects.iter().find_map(|&(usable, code)| {
+        code.filter(|_| usable)
+    }).unwrap_or(BYTECODE)
+}
+
 #[cfg(target_arch = "aarch64")]
 const BYTECODE: &[u8] = include_bytes!("../bpf/bytecode.arm64.o");
 
 #[cfg(target_arch = "x86_64")]
 const BYTECODE: &[u8] = include_bytes!("../bpf/bytecode.x86.o");
+
+#[cfg(all(feature = "ringbuf", target_arch = "aarch64"))]
+const RINGBUF: Option<&[u8]> = Some(include_bytes!("../bpf/bytecode.ringbuf.arm64.o"));
+
+#[cfg(all(feature = "ringbuf", target_arch = "x86_64"))]
+const RINGBUF: Option<&[u8]> = Some(include_bytes!("../bpf/bytecode.ringbuf.x86.o"));
+
+#[cfg(not(feature = "ringbuf"))]
+const RINGBUF: Option<&[u8]> = None;
+
+#[cfg(all(feature = "core", target_arch = "aarch64"))]
+const CORE: Option<&[u8]> = Some(include_bytes!("../bpf/bytecode.core.arm64.o"));
+
+#[cfg(all(feature = "core", target_arch = "x86_64"))]
+const CORE: Option<&[u8]> = Some(include_bytes!("../bpf/bytecode.core.x86.o"));
+
+#[cfg(not(feature = "core"))]
+const CORE: Option<&[u8]> = None;
+
+#[cfg(all(feature = "core", feature = "ringbuf", target_arch = "aarch64"))]
+const CORE_RINGBUF: Option<&[u8]> = Some(include_bytes!("../bpf/bytecode.core.ringbuf.arm64.o"));
+
+#[cfg(all(feature = "core", feature = "ringbuf", target_arch = "x86_64"))]
+const CORE_RINGBUF: Option<&[u8]> = Some(include_bytes!("../bpf/bytecode.core.ringbuf.x86.o"));
+
+#[cfg(not(all(feature = "core", feature = "ringbuf")))]
+const CORE_RINGBUF: Option<&[u8]> = None;
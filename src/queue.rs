@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use anyhow::{anyhow, Error, Result};
+use log::{debug, error};
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::mpsc::error::TrySendError;
+use crate::stats::Stats;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Policy {
+    Block,
+    DropNewest,
+    DropOldest,
+}
+
+pub struct Queue<T> {
+    name:    &'static str,
+    tx:      Sender<T>,
+    policy:  Policy,
+    shared:  Arc<Shared<T>>,
+    stats:   Arc<Stats>,
+}
+
+struct Shared<T> {
+    pending: Arc<Pending<T>>,
+}
+
+struct Pending<T> {
+    items:    Mutex<VecDeque<T>>,
+    notify:   Notify,
+    capacity: usize,
+    closed:   AtomicBool,
+}
+
+impl<T: Send + 'static> Queue<T> {
+    pub fn new(name: &'static str, capacity: usize, policy: Policy, stats: Arc<Stats>) -> (Self, Receiver<T>) {
+        let (tx, rx) = channel(capacity);
+
+        let pending = Arc::new(Pending {
+            items:    Mutex::new(VecDeque::with_capacity(capacity)),
+            notify:   Notify::new(),
+            capacity,
+            closed:   AtomicBool::new(false),
+        });
+
+        if policy == Policy::DropOldest {
+            let pending = pending.clone();
+            let tx      = tx.clone();
+            tokio::spawn(async move {
+                match forward(pending, tx).await {
+                    Ok(()) => debug!("{} queue finished", name),
+                    Err(e) => error!("{} queue failed: {:?}", name, e),
+                }
+            });
+        }
+
+        let shared = Arc::new(Shared { pending });
+
+        (Self { name, tx, policy, shared, stats }, rx)
+    }
+
+    pub async fn send(&self, item: T) -> Result<()> {
+        match self.policy {
+            Policy::Block => match self.tx.try_send(item) {
+                Ok(())                        => (),
+                Err(TrySendError::Full(item)) => {
+                    self.stats.blocked(self.name);
+                    self.tx.send(item).await.map_err(|_| self.closed())?;
+                }
+                Err(TrySendError::Closed(_))  => return Err(self.closed()),
+            },
+            Policy::DropNewest => match self.tx.try_send(item) {
+                Ok(())                       => (),
+                Err(TrySendError::Full(_))   => self.stats.dropped(self.name),
+                Err(TrySendError::Closed(_)) => return Err(self.closed()),
+            },
+            Policy::DropOldest => {
+                let pending   = &self.shared.pending;
+                let mut items = pending.items.lock();
+                if items.len() >= pending.capacity {
+                    items.pop_front();
+                    self.stats.dropped(self.name);
+                }
+                items.push_back(item);
+                drop(items);
+                pending.notify.notify_one();
+            }
+        }
+        Ok(())
+    }
+
+    fn closed(&self) -> Error {
+        anyhow!("{} queue closed", self.name)
+    }
+}
+
+impl<T> Clone for Queue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            name:    self.name,
+            tx:      self.tx.clone(),
+            policy:  self.policy,
+            shared:  self.shared.clone(),
+            stats:   self.stats.clone(),
+        }
+    }
+}
+
+async fn forward<T>(pending: Arc<Pending<T>>, tx: Sender<T>) -> Result<()> {
+    loop {
+        let item = pending.items.lock().pop_front();
+        match item {
+            Some(item) => tx.send(item).await.map_err(|_| anyhow!("queue closed"))?,
+            None       => {
+                if pending.closed.load(Ordering::Acquire) {
+                    return Ok(());
+                }
+                pending.notify.notified().await;
+            }
+        }
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        self.pending.closed.store(true, Ordering::Release);
+        self.pending.notify.notify_one();
+    }
+}
+
+impl FromStr for Policy {
+    type Err = Error;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        match arg {
+            "block"       => Ok(Self::Block),
+            "drop-newest" => Ok(Self::DropNewest),
+            "drop-oldest" => Ok(Self::DropOldest),
+            _             => Err(anyhow!("invalid policy: {}", arg)),
+        }
+    }
+}
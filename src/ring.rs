@@ -0,0 +1,203 @@
+use std::convert::TryFrom;
+use std::io::Error;
+use std::mem::size_of;
+use std::os::unix::io::RawFd;
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use anyhow::{anyhow, Result};
+use bytes::BytesMut;
+use libc::{c_void, MAP_FAILED, MAP_SHARED, PROT_READ, PROT_WRITE};
+use tokio::io::unix::AsyncFd;
+
+pub struct RingBuf {
+    fd:       AsyncFd<RawFd>,
+    consumer: *mut c_void,
+    producer: *mut c_void,
+    page:     usize,
+    mask:     usize,
+}
+
+unsafe impl Send for RingBuf {}
+
+#[repr(C)]
+struct MapAttr {
+    map_type:    u32,
+    key_size:    u32,
+    value_size:  u32,
+    max_entries: u32,
+    map_flags:   u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct MapInfo {
+    map_type:    u32,
+    id:          u32,
+    key_size:    u32,
+    value_size:  u32,
+    max_entries: u32,
+    map_flags:   u32,
+    name:        [u8; 16],
+}
+
+#[repr(C)]
+struct InfoAttr {
+    fd:   u32,
+    len:  u32,
+    info: u64,
+}
+
+impl RingBuf {
+    pub fn open(fd: RawFd, size: usize) -> Result<Self> {
+        let page = page();
+
+        if !size.is_power_of_two() || size < page {
+            return Err(anyhow!("invalid ringbuf size: {}", size));
+        }
+
+        let consumer = mmap(fd, page, PROT_READ | PROT_WRITE, 0)?;
+        let producer = match mmap(fd, page + 2 * size, PROT_READ, page) {
+            Ok(producer) => producer,
+            Err(e)       => {
+                unsafe { libc::munmap(consumer, page) };
+                return Err(e);
+            }
+        };
+
+        let fd   = AsyncFd::new(fd)?;
+        let mask = size - 1;
+
+        Ok(Self { fd, consumer, producer, page, mask })
+    }
+
+    pub async fn read_events(&mut self, bufs: &mut Vec<BytesMut>) -> Result<usize> {
+        loop {
+            let n = self.drain(bufs);
+            if n > 0 {
+                return Ok(n);
+            }
+
+            let mut guard = self.fd.readable().await?;
+            guard.clear_ready();
+        }
+    }
+
+    fn drain(&mut self, bufs: &mut Vec<BytesMut>) -> usize {
+        let consumer = unsafe { &*(self.consumer as *const AtomicUsize) };
+        let producer = unsafe { &*(self.producer as *const AtomicUsize) };
+        let data     = unsafe { (self.producer as *const u8).add(self.page) };
+
+        let mut pos = consumer.load(Ordering::Acquire);
+        let end     = producer.load(Ordering::Acquire);
+
+        bufs.clear();
+
+        while pos < end {
+            let head = unsafe { data.add(pos & self.mask) };
+            let len  = unsafe { &*(head as *const AtomicU32) }.load(Ordering::Acquire);
+
+            if len & BUSY != 0 {
+                break;
+            }
+
+            let size = (len & !(BUSY | DISCARD)) as usize;
+
+            if len & DISCARD == 0 {
+                let sample = unsafe { slice::from_raw_parts(head.add(HEADER), size) };
+                bufs.push(BytesMut::from(sample));
+            }
+
+            pos += (size + HEADER + 7) & !7;
+            consumer.store(pos, Ordering::Release);
+        }
+
+        bufs.len()
+    }
+}
+
+impl Drop for RingBuf {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.consumer, self.page);
+            libc::munmap(self.producer, self.page + 2 * (self.mask + 1));
+        }
+    }
+}
+
+pub fn supported() -> bool {
+    let attr = MapAttr {
+        map_type:    BPF_MAP_TYPE_RINGBUF,
+        key_size:    0,
+        value_size:  0,
+        max_entries: page() as u32,
+        map_flags:   0,
+    };
+
+    let fd = unsafe {
+        libc::syscall(libc::SYS_bpf, BPF_MAP_CREATE, &attr as *const MapAttr, size_of::<MapAttr>())
+    };
+
+    if fd < 0 {
+        return false;
+    }
+
+    unsafe { libc::close(fd as RawFd) };
+
+    true
+}
+
+pub fn size(fd: RawFd) -> Result<Option<usize>> {
+    let info = info(fd).ok_or_else(|| {
+        anyhow!("map info failed: {}", Error::last_os_error())
+    })?;
+
+    if info.map_type != BPF_MAP_TYPE_RINGBUF {
+        return Ok(None);
+    }
+
+    Ok(Some(usize::try_from(info.max_entries)?))
+}
+
+fn info(fd: RawFd) -> Option<MapInfo> {
+    let mut info = MapInfo::default();
+
+    let attr = InfoAttr {
+        fd:   u32::try_from(fd).ok()?,
+        len:  size_of::<MapInfo>() as u32,
+        info: &mut info as *mut MapInfo as u64,
+    };
+
+    let rc = unsafe {
+        libc::syscall(libc::SYS_bpf, BPF_OBJ_GET_INFO_BY_FD, &attr as *const InfoAttr, size_of::<InfoAttr>())
+    };
+
+    match rc {
+        0 => Some(info),
+        _ => None,
+    }
+}
+
+fn mmap(fd: RawFd, len: usize, prot: i32, offset: usize) -> Result<*mut c_void> {
+    let addr = unsafe {
+        libc::mmap(ptr::null_mut(), len, prot, MAP_SHARED, fd, offset as libc::off_t)
+    };
+
+    if addr == MAP_FAILED {
+        return Err(anyhow!("ringbuf mmap failed: {}", Error::last_os_error()));
+    }
+
+    Ok(addr)
+}
+
+fn page() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+const BPF_MAP_CREATE:         libc::c_long = 0;
+const BPF_OBJ_GET_INFO_BY_FD: libc::c_long = 15;
+const BPF_MAP_TYPE_RINGBUF:   u32          = 27;
+
+const BUSY:    u32   = 1 << 31;
+const DISCARD: u32   = 1 << 30;
+const HEADER:  usize = 8;
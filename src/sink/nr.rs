@@ -1,7 +1,7 @@
 use std::convert::{TryFrom, TryInto};
 use std::mem;
 use std::sync::Arc;
-use std::time::{UNIX_EPOCH, Duration};
+use std::time::{SystemTime, UNIX_EPOCH, Duration};
 use anyhow::{anyhow, Result};
 use flate2::{Compression, write::GzEncoder};
 use log::{debug, error, warn};
@@ -11,6 +11,7 @@ use reqwest::header::{CONTENT_TYPE, CONTENT_ENCODING, HeaderMap, HeaderValue};
 use serde_json::json;
 use tokio::time::interval;
 use crate::data::Record;
+use crate::stats::Stats;
 use super::Args;
 
 pub struct NewRelicClient {
@@ -21,6 +22,7 @@ struct Sender {
     client:   HttpClient,
     endpoint: Url,
     records:  Mutex<Vec<Record>>,
+    stats:    Mutex<Option<Arc<Stats>>>,
 }
 
 impl NewRelicClient {
@@ -61,12 +63,21 @@ impl NewRelicClient {
         self.sender.push(record);
         Ok(())
     }
+
+    pub fn attach(&self, stats: Arc<Stats>) {
+        *self.sender.stats.lock() = Some(stats);
+    }
+
+    pub async fn flush(&self) -> Result<()> {
+        self.sender.flush().await
+    }
 }
 
 impl Sender {
     fn new(client: HttpClient, endpoint: Url) -> Self {
         let records = Mutex::new(Vec::new());
-        Self { client, endpoint, records }
+        let stats   = Mutex::new(None);
+        Self { client, endpoint, records, stats }
     }
 
     fn push(&self, record: Record) {
@@ -84,55 +95,128 @@ impl Sender {
 
         loop {
             interval.tick().await;
+            self.flush().await?;
+        }
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let mut payload = self.drain().iter().map(|record| {
+            let (id, name, image) = record.process.container.as_ref().map(|c| {
+                (c.id.as_str(), c.name.as_str(), c.image.as_str())
+            }).unwrap_or_default();
+
+            let (pod, namespace) = record.pod.as_ref().map(|p| {
+                (p.name.as_str(), p.namespace.as_str())
+            }).unwrap_or_default();
+
+            let timestamp = record.timestamp.duration_since(UNIX_EPOCH)?;
+            let timestamp = u64::try_from(timestamp.as_millis())?;
+            let srtt      = u64::try_from(record.srtt.as_micros())?;
+            let ancestry  = record.ancestry.iter().map(|p| {
+                format!("{}:{}", p.pid, p.comm)
+            }).collect::<Vec<_>>().join(",");
+
+            let mut event = json!({
+                "eventType":        "ContainerVisibility",
+                "timestamp":        timestamp,
+                "event":            &record.event,
+                "protocol":         format!("{:?}", record.proto),
+                "source.ip":        record.src.ip(),
+                "source.port":      record.src.port(),
+                "source.host":      &record.hostname,
+                "destination.ip":   record.dst.ip(),
+                "destination.port": record.dst.port(),
+                "process.pid":      record.process.pid,
+                "process.ppid":     record.process.ppid,
+                "process.cmd":      &record.process.command.join(" "),
+                "process.exe":      &record.process.exe,
+                "process.comm":     &record.process.comm,
+                "process.tid":      record.tid,
+                "process.uid":      record.process.uid,
+                "process.gid":      record.process.gid,
+                "process.resolved": record.process.resolved,
+                "process.ancestry": ancestry,
+                "container.id":     id,
+                "container.name":   name,
+                "container.image":  image,
+                "k8s.pod":          pod,
+                "k8s.namespace":    namespace,
+                "netns":            record.netns,
+                "bytes.rx":         record.rx,
+                "bytes.tx":         record.tx,
+                "bytes.rx.delta":   record.rx_delta,
+                "bytes.tx.delta":   record.tx_delta,
+                "packets.rx":       record.rx_pkts,
+                "packets.tx":       record.tx_pkts,
+                "tcp.srtt":         srtt,
+                "tcp.retransmits":  record.retx,
+                "error":            record.error.map(|e| e.to_string()),
+            });
+
+            if let Some(latency) = record.handshake_latency {
+                event["tcp.handshake_latency"] = json!(u64::try_from(latency.as_micros())?);
+            }
+
+            if let Some(buildup) = record.buildup {
+                event["tcp.time_wait"]  = json!(buildup.time_wait);
+                event["tcp.close_wait"] = json!(buildup.close_wait);
+            }
+
+            if let Some(flow) = &record.flow {
+                let start    = flow.start.duration_since(UNIX_EPOCH)?;
+                let start    = u64::try_from(start.as_millis())?;
+                let duration = u64::try_from(flow.duration.as_millis())?;
+
+                event["flow.start"]     = json!(start);
+                event["flow.duration"]  = json!(duration);
+                event["flow.direction"] = json!(format!("{:?}", flow.direction));
+            }
+
+            Ok(event)
+        }).collect::<Result<Vec<_>>>()?;
 
-            let payload = self.drain().iter().map(|record| {
-                let (id, name, image) = record.process.container.as_ref().map(|c| {
-                    (c.id.as_str(), c.name.as_str(), c.image.as_str())
-                }).unwrap_or_default();
-
-                let timestamp = record.timestamp.duration_since(UNIX_EPOCH)?;
-                let timestamp = u64::try_from(timestamp.as_millis())?;
-                let srtt      = u64::try_from(record.srtt.as_micros())?;
-
-                Ok(json!({
-                    "eventType":        "ContainerVisibility",
-                    "timestamp":        timestamp,
-                    "event":            &record.event,
-                    "source.ip":        record.src.ip(),
-                    "source.port":      record.src.port(),
-                    "source.host":      &record.hostname,
-                    "destination.ip":   record.dst.ip(),
-                    "destination.port": record.dst.port(),
-                    "process.pid":      record.process.pid,
-                    "process.cmd":      &record.process.command.join(" "),
-                    "container.id":     id,
-                    "container.name":   name,
-                    "container.image":  image,
-                    "bytes.rx":         record.rx,
-                    "bytes.tx":         record.tx,
-                    "tcp.srtt":         srtt,
-                    "tcp.retransmits":  record.retx,
-                }))
-            }).collect::<Result<Vec<_>>>()?;
-
-            debug!("sending {} records", payload.len());
-
-            for chunk in payload.chunks(2000) {
-                let mut e = GzEncoder::new(Vec::new(), Compression::default());
-                serde_json::to_writer(&mut e, chunk)?;
-                let body = e.finish()?;
-
-                let endpoint = self.endpoint.clone();
-                let mut req  = Request::new(Method::POST, endpoint);
-                *req.body_mut() = Some(body.into());
-
-                let res = self.client.execute(req).await?;
-
-                if !res.status().is_success() {
-                    let body = res.text().await?;
-                    warn!("send failed: {}", body);
+        let stats = self.stats.lock().clone();
+
+        if let Some(stats) = stats {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+            let now = u64::try_from(now.as_millis())?;
+
+            for counter in stats.counters() {
+                let mut event = json!({
+                    "eventType":   "ContainerVisibilityStats",
+                    "timestamp":   now,
+                    "source.host": &stats.hostname,
+                    "metric":      counter.name,
+                    "value":       counter.value,
+                });
+
+                for (name, value) in counter.labels {
+                    event[name] = json!(value);
                 }
+
+                payload.push(event);
             }
         }
+
+        debug!("sending {} records", payload.len());
+
+        for chunk in payload.chunks(2000) {
+            let mut e = GzEncoder::new(Vec::new(), Compression::default());
+            serde_json::to_writer(&mut e, chunk)?;
+            let body = e.finish()?;
+
+            let endpoint = self.endpoint.clone();
+            let mut req  = Request::new(Method::POST, endpoint);
+            *req.body_mut() = Some(body.into());
+
+            let res = self.client.execute(req).await?;
+
+            if !res.status().is_success() {
+                let body = res.text().await?;
+                warn!("send failed: {}", body);
+            }
+        }
+
+        Ok(())
     }
 }
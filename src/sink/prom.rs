@@ -1,8 +1,9 @@
+use std::collections::BTreeMap;
 use std::convert::{TryFrom, TryInto};
 use std::io::Write;
 use std::mem;
 use std::sync::Arc;
-use std::time::{UNIX_EPOCH, Duration};
+use std::time::{SystemTime, UNIX_EPOCH, Duration};
 use anyhow::Result;
 use base64::{STANDARD, write::EncoderStringWriter};
 use log::{debug, error, warn};
@@ -12,7 +13,8 @@ use reqwest::{Client as HttpClient, Method, Request, Url};
 use reqwest::header::{self, HeaderMap, HeaderValue};
 use snap::raw::Encoder;
 use tokio::time::interval;
-use crate::data::Record;
+use crate::data::{Proto, Record};
+use crate::stats::Stats;
 use super::Args;
 
 pub struct PrometheusClient {
@@ -23,6 +25,19 @@ struct Sender {
     client:   HttpClient,
     endpoint: Url,
     records:  Mutex<Vec<Record>>,
+    counters: Mutex<Counters>,
+    stats:    Mutex<Option<Arc<Stats>>>,
+}
+
+#[derive(Default)]
+struct Counters {
+    series: BTreeMap<Vec<Label>, Counter>,
+    flush:  u64,
+}
+
+struct Counter {
+    value: f64,
+    seen:  u64,
 }
 
 impl PrometheusClient {
@@ -65,8 +80,44 @@ impl PrometheusClient {
         self.sender.push(record);
         Ok(())
     }
+
+    pub fn attach(&self, stats: Arc<Stats>) {
+        *self.sender.stats.lock() = Some(stats);
+    }
+
+    pub async fn flush(&self) -> Result<()> {
+        self.sender.flush().await
+    }
 }
 
+const COUNTER_LABELS: &[&str] = &[
+    "protocol",
+    "source_host",
+    "destination_ip",
+    "container_id",
+    "container_name",
+    "container_image",
+    "k8s_pod",
+    "k8s_namespace",
+    "netns",
+];
+
+const FAILURE_LABELS: &[&str] = &[
+    "protocol",
+    "source_host",
+    "destination_ip",
+    "destination_port",
+    "container_name",
+    "k8s_namespace",
+    "error",
+];
+
+const COUNTER_IDLE: u64 = 30;
+
+const LATENCY_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
 #[derive(Message)]
 pub struct WriteRequest {
     #[prost(message, repeated)]
@@ -97,10 +148,29 @@ pub struct Sample {
     pub timestamp: i64,
 }
 
+impl Counters {
+    fn add(&mut self, labels: Vec<Label>, value: f64) {
+        let flush   = self.flush;
+        let counter = self.series.entry(labels).or_insert(Counter {
+            value: 0.0,
+            seen:  flush,
+        });
+        counter.value += value;
+        counter.seen   = flush;
+    }
+
+    fn expire(&mut self) {
+        let flush = self.flush;
+        self.series.retain(|_, counter| counter.seen + COUNTER_IDLE > flush);
+    }
+}
+
 impl Sender {
     fn new(client: HttpClient, endpoint: Url) -> Self {
-        let records = Mutex::new(Vec::new());
-        Self { client, endpoint, records }
+        let records  = Mutex::new(Vec::new());
+        let counters = Mutex::new(Counters::default());
+        let stats    = Mutex::new(None);
+        Self { client, endpoint, records, counters, stats }
     }
 
     fn push(&self, record: Record) {
@@ -118,94 +188,223 @@ impl Sender {
 
         loop {
             interval.tick().await;
+            self.flush().await?;
+        }
+    }
 
-            let records    = self.drain();
-            let mut series = Vec::with_capacity(records.len() * 2);
-
-            for record in records {
-                let timestamp = record.timestamp.duration_since(UNIX_EPOCH)?;
-                let secs = i64::try_from(timestamp.as_secs())? * 1000;
-                let ms   = i64::try_from(timestamp.subsec_millis())?;
-                let timestamp = secs + ms;
-
-                let mut labels = Vec::new();
-                let mut label  = |name: &str, value: String| {
-                    labels.push(Label {
-                        name:  name.to_owned(),
-                        value: value,
-                    })
-                };
-
-                label("event",            record.event);
-                label("source_ip",        record.src.ip().to_string());
-                label("source_port",      record.src.port().to_string());
-                label("source_host",      record.hostname.to_string());
-                label("destination_ip",   record.dst.ip().to_string());
-                label("destination_port", record.dst.port().to_string());
-                label("process_pid",      record.process.pid.to_string());
-                label("process_cmd",      record.process.command.join(" "));
-
-                if let Some(container) = &record.process.container {
-                    label("container_id",    container.id.to_string());
-                    label("container_name",  container.name.to_string());
-                    label("container_image", container.image.to_string());
-                }
+    async fn flush(&self) -> Result<()> {
+        let series = self.series(self.drain())?;
 
-                if let Some(pod) = &record.process.pod {
-                    label("k8s_pod", pod.name.to_string());
-                    label("k8s_namespace", pod.namespace.to_string());
-                }
+        debug!("sending {} records", series.len());
 
-                let mut labels0 = labels.clone();
-                labels0.push(Label {
-                    name:  "__name__".to_owned(),
-                    value: "bytes_rx".to_owned(),
-                });
-                labels0.sort_unstable();
+        let mut buf = Vec::new();
+        WriteRequest {
+            timeseries: series,
+        }.encode(&mut buf)?;
+
+        let body = Encoder::new().compress_vec(&buf)?;
+
+        let endpoint = self.endpoint.clone();
+        let mut req  = Request::new(Method::POST, endpoint);
+        *req.body_mut() = Some(body.into());
+
+        let res = self.client.execute(req).await?;
+
+        if !res.status().is_success() {
+            let body = res.text().await?;
+            warn!("send failed: {}", body);
+        }
+
+        Ok(())
+    }
+
+    fn series(&self, records: Vec<Record>) -> Result<Vec<TimeSeries>> {
+        let mut counters = self.counters.lock();
+        let mut series   = Vec::with_capacity(records.len() * 2);
+
+        counters.flush += 1;
+
+        for record in records {
+            let timestamp = record.timestamp.duration_since(UNIX_EPOCH)?;
+            let secs = i64::try_from(timestamp.as_secs())? * 1000;
+            let ms   = i64::try_from(timestamp.subsec_millis())?;
+            let timestamp = secs + ms;
+
+            let mut labels = Vec::new();
+            let mut label  = |name: &str, value: String| {
+                labels.push(Label {
+                    name:  name.to_owned(),
+                    value: value,
+                })
+            };
+
+            label("event",            record.event.clone());
+            label("protocol",         format!("{:?}", record.proto));
+            label("source_ip",        record.src.ip().to_string());
+            label("source_port",      record.src.port().to_string());
+            label("source_host",      record.hostname.to_string());
+            label("destination_ip",   record.dst.ip().to_string());
+            label("destination_port", record.dst.port().to_string());
+            label("process_pid",      record.process.pid.to_string());
+            label("process_cmd",      record.process.command.join(" "));
+            label("process_comm",     record.process.comm.clone());
+            label("process_resolved", record.process.resolved.to_string());
+
+            if let Some(container) = &record.process.container {
+                label("container_id",    container.id.to_string());
+                label("container_name",  container.name.to_string());
+                label("container_image", container.image.to_string());
+            }
+
+            label("netns",            record.netns.to_string());
+
+            if let Some(pod) = &record.pod {
+                label("k8s_pod", pod.name.to_string());
+                label("k8s_namespace", pod.namespace.to_string());
+            }
+
+            if let Some(flow) = &record.flow {
+                label("direction", format!("{:?}", flow.direction));
+            }
+
+            if let Some(error) = &record.error {
+                label("error", error.to_string());
+            }
 
-                let mut labels1 = labels;
-                labels1.push(Label {
+            let select = |names: &[&str]| labels.iter().filter(|label| {
+                names.contains(&label.name.as_str())
+            }).cloned().collect::<Vec<_>>();
+
+            let keys     = select(COUNTER_LABELS);
+            let failures = select(FAILURE_LABELS);
+
+            let mut count = |name: &str, keys: &[Label], extra: &[(&str, String)], value: f64| {
+                let mut labels = keys.to_vec();
+                labels.extend(extra.iter().map(|(name, value)| Label {
+                    name:  name.to_string(),
+                    value: value.clone(),
+                }));
+                labels.push(Label {
                     name:  "__name__".to_owned(),
-                    value: "bytes_tx".to_owned(),
+                    value: name.to_owned(),
                 });
-                labels1.sort_unstable();
+                labels.sort_unstable();
 
-                series.push(TimeSeries {
-                    labels: labels0,
-                    samples: vec![Sample {
-                        value:     record.rx.try_into()?,
-                        timestamp: timestamp,
-                    }],
+                counters.add(labels, value);
+            };
+
+            count("bytes_rx_total", &keys, &[], record.rx_delta as f64);
+            count("bytes_tx_total", &keys, &[], record.tx_delta as f64);
+
+            if record.error.is_some() {
+                count("connect_failures_total", &failures, &[], 1.0);
+            }
+
+            match record.event.as_str() {
+                "Retransmit"   => count("tcp_retransmits_total", &keys, &[], 1.0),
+                "SendReset"    => count("tcp_resets_sent_total", &keys, &[], 1.0),
+                "ReceiveReset" => count("tcp_resets_received_total", &keys, &[], 1.0),
+                _              => (),
+            }
+
+            if let Some(latency) = record.handshake_latency {
+                let secs = latency.as_secs_f64();
+
+                for le in LATENCY_BUCKETS {
+                    let hit = if secs <= *le { 1.0 } else { 0.0 };
+                    count("tcp_handshake_latency_seconds_bucket", &keys, &[("le", le.to_string())], hit);
+                }
+
+                count("tcp_handshake_latency_seconds_bucket", &keys, &[("le", "+Inf".to_string())], 1.0);
+                count("tcp_handshake_latency_seconds_sum", &keys, &[], secs);
+                count("tcp_handshake_latency_seconds_count", &keys, &[], 1.0);
+            }
+
+            let mut metrics = vec![
+                ("bytes_rx", record.rx as f64),
+                ("bytes_tx", record.tx as f64),
+            ];
+
+            if record.proto == Proto::Udp {
+                metrics.push(("packets_rx", f64::from(record.rx_pkts)));
+                metrics.push(("packets_tx", f64::from(record.tx_pkts)));
+            }
+
+            if let Some(flow) = &record.flow {
+                metrics.push(("flow_duration_seconds", flow.duration.as_secs_f64()));
+            }
+
+            if record.event == "Listen" {
+                metrics.push(("tcp_listening", 1.0));
+            }
+
+            if let Some(buildup) = record.buildup {
+                metrics.push(("tcp_time_wait", f64::from(buildup.time_wait)));
+                metrics.push(("tcp_close_wait", f64::from(buildup.close_wait)));
+            }
+
+            for (name, value) in metrics {
+                let mut labels = labels.clone();
+                labels.push(Label {
+                    name:  "__name__".to_owned(),
+                    value: name.to_owned(),
                 });
+                labels.sort_unstable();
 
                 series.push(TimeSeries {
-                    labels: labels1,
+                    labels,
                     samples: vec![Sample {
-                        value:     record.rx.try_into()?,
-                        timestamp: timestamp,
+                        value,
+                        timestamp,
                     }],
                 });
             }
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let now = i64::try_from(now.as_millis())?;
 
-            debug!("sending {} records", series.len());
+        counters.expire();
 
-            let mut buf = Vec::new();
-            WriteRequest {
-                timeseries: series,
-            }.encode(&mut buf)?;
+        for (labels, counter) in counters.series.iter() {
+            series.push(TimeSeries {
+                labels:  labels.clone(),
+                samples: vec![Sample {
+                    value:     counter.value,
+                    timestamp: now,
+                }],
+            });
+        }
 
-            let body = Encoder::new().compress_vec(&buf)?;
+        let stats = self.stats.lock().clone();
 
-            let endpoint = self.endpoint.clone();
-            let mut req  = Request::new(Method::POST, endpoint);
-            *req.body_mut() = Some(body.into());
+        if let Some(stats) = stats {
+            for counter in stats.counters() {
+                let mut labels = counter.labels.into_iter().map(|(name, value)| Label {
+                    name: name.to_owned(),
+                    value,
+                }).collect::<Vec<_>>();
 
-            let res = self.client.execute(req).await?;
+                labels.push(Label {
+                    name:  "source_host".to_owned(),
+                    value: stats.hostname.to_string(),
+                });
+                labels.push(Label {
+                    name:  "__name__".to_owned(),
+                    value: counter.name.to_owned(),
+                });
+                labels.sort_unstable();
 
-            if !res.status().is_success() {
-                let body = res.text().await?;
-                warn!("send failed: {}", body);
+                series.push(TimeSeries {
+                    labels,
+                    samples: vec![Sample {
+                        value:     counter.value as f64,
+                        timestamp: now,
+                    }],
+                });
             }
         }
+
+        Ok(series)
     }
 }
@@ -1,6 +1,9 @@
+use std::io::{self, Write};
 use std::str::FromStr;
+use std::sync::Arc;
 use anyhow::{Error, Result, anyhow};
 use crate::data::Record;
+use crate::stats::Stats;
 use super::Args;
 use super::nr::NewRelicClient;
 use super::prom::PrometheusClient;
@@ -20,6 +23,23 @@ impl Sink {
         }
         Ok(())
     }
+
+    pub fn attach(&self, stats: Arc<Stats>) {
+        match self {
+            Self::NewRelic(c)   => c.attach(stats),
+            Self::Prometheus(c) => c.attach(stats),
+            Self::Stdout        => (),
+        }
+    }
+
+    pub async fn flush(&self) -> Result<()> {
+        match self {
+            Self::NewRelic(c)   => c.flush().await?,
+            Self::Prometheus(c) => c.flush().await?,
+            Self::Stdout        => io::stdout().flush()?,
+        }
+        Ok(())
+    }
 }
 
 impl Default for Sink {
@@ -0,0 +1,230 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use anyhow::{anyhow, Result};
+use libc::pid_t;
+use log::debug;
+use crate::data::{Proto, TcpState};
+use crate::event::{normalize, Call, Sock};
+
+#[derive(Debug)]
+pub struct Socket {
+    pub pid:   pid_t,
+    pub netns: u64,
+    pub src:   SocketAddr,
+    pub dst:   SocketAddr,
+    pub state: TcpState,
+}
+
+impl Socket {
+    pub fn sock(self, call: Call, ts: u64) -> Sock {
+        Sock {
+            call, ts,
+            pid:               self.pid,
+            cgroup:            0,
+            netns:             self.netns,
+            task:              None,
+            proto:             Proto::Tcp,
+            src:               self.src,
+            dst:               self.dst,
+            rx:                0,
+            tx:                0,
+            rx_delta:          0,
+            tx_delta:          0,
+            rx_pkts:           0,
+            tx_pkts:           0,
+            srtt:              0,
+            retx:              0,
+            error:             None,
+            handshake_latency: None,
+            buildup:           None,
+            flow:              None,
+        }
+    }
+}
+
+pub fn sockets(root: &Path) -> Result<Vec<Socket>> {
+    let pids = fs::read_dir(root)?.flatten().filter_map(|entry| {
+        entry.file_name().to_str()?.parse::<pid_t>().ok()
+    }).collect::<Vec<_>>();
+
+    let mut seen  = HashSet::new();
+    let mut table = HashMap::new();
+
+    for &pid in &pids {
+        let netns = match fs::metadata(root.join(format!("{}/ns/net", pid))) {
+            Ok(meta) => meta.ino(),
+            Err(_)   => continue,
+        };
+
+        if !seen.insert(netns) {
+            continue;
+        }
+
+        for name in &["tcp", "tcp6"] {
+            let path = root.join(format!("{}/net/{}", pid, name));
+            if let Ok(data) = fs::read_to_string(&path) {
+                for (inode, src, dst, state) in parse(&data) {
+                    table.insert(inode, (netns, src, dst, state));
+                }
+            }
+        }
+    }
+
+    let mut sockets = Vec::new();
+
+    for &pid in &pids {
+        for inode in inodes(root, pid) {
+            if let Some((netns, src, dst, state)) = table.remove(&inode) {
+                sockets.push(Socket { pid, netns, src, dst, state });
+            }
+        }
+    }
+
+    debug!("found {} sockets in {} namespaces", sockets.len(), seen.len());
+
+    Ok(sockets)
+}
+
+fn inodes(root: &Path, pid: pid_t) -> Vec<u64> {
+    let fds = match fs::read_dir(root.join(format!("{}/fd", pid))) {
+        Ok(fds) => fds,
+        Err(_)  => return Vec::new(),
+    };
+
+    fds.flatten().filter_map(|fd| {
+        let link = fs::read_link(fd.path()).ok()?;
+        let link = link.to_str()?;
+        link.strip_prefix("socket:[")?.strip_suffix(']')?.parse().ok()
+    }).collect()
+}
+
+fn parse(data: &str) -> Vec<(u64, SocketAddr, SocketAddr, TcpState)> {
+    data.lines().skip(1).filter_map(|line| match entry(line) {
+        Ok(entry) => Some(entry),
+        Err(e)    => {
+            debug!("skipping socket {:?}: {}", line, e);
+            None
+        }
+    }).collect()
+}
+
+fn entry(line: &str) -> Result<(u64, SocketAddr, SocketAddr, TcpState)> {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+
+    if fields.len() < 10 {
+        return Err(anyhow!("expected 10 fields, found {}", fields.len()));
+    }
+
+    let src   = address(fields[1])?;
+    let dst   = address(fields[2])?;
+    let state = TcpState::from(u32::from_str_radix(fields[3], 16)?);
+    let inode = fields[9].parse()?;
+
+    Ok((inode, src, dst, state))
+}
+
+fn address(field: &str) -> Result<SocketAddr> {
+    let (addr, port) = match field.split_once(':') {
+        Some(split) => split,
+        None        => return Err(anyhow!("invalid address: {}", field)),
+    };
+
+    if addr.len() % 8 != 0 {
+        return Err(anyhow!("invalid address: {}", field));
+    }
+
+    let words = (0..addr.len()).step_by(8).map(|i| {
+        Ok(u32::from_str_radix(&addr[i..i + 8], 16)?.to_ne_bytes())
+    }).collect::<Result<Vec<_>>>()?;
+
+    let addr = match words[..] {
+        [word] => IpAddr::from(Ipv4Addr::from(word)),
+        [..]   => {
+            let bytes: [u8; 16] = words.concat().try_into().map_err(|_| {
+                anyhow!("invalid address: {}", field)
+            })?;
+            normalize(Ipv6Addr::from(bytes))
+        }
+    };
+
+    let port = u16::from_str_radix(port, 16)?;
+
+    Ok(SocketAddr::new(addr, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+    use super::*;
+
+    #[test]
+    fn fixture() {
+        let root = std::env::temp_dir().join(format!("convis-snapshot-{}", std::process::id()));
+        fs::remove_dir_all(&root).ok();
+
+        let tcp = [
+            "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode",
+            "   0: 00000000:0016 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1000 1 0000000000000000 100 0 0 10 0",
+            "   1: 0100000A:0016 0200000A:C350 01 00000000:00000000 00:00000000 00000000     0        0 1001 1 0000000000000000 20 4 30 10 -1",
+            "   2: garbage",
+            "   3: 0100000A:1F90 0300000A:D431 01 00000000:00000000 00:00000000 00000000     0        0 1003 1 0000000000000000 20 4 30 10 -1",
+        ].join("\n");
+
+        let tcp6 = [
+            "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode",
+            "   0: 0000000000000000FFFF00000100000A:01BB 0000000000000000FFFF00000400000A:E290 01 00000000:00000000 00:00000000 00000000     0        0 1002 1 0000000000000000 20 4 30 10 -1",
+            "   1: 00000000000000000000000001000000:0050 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 not-an-inode 1 0000000000000000 100 0 0 10 0",
+        ].join("\n");
+
+        process(&root, 100, &tcp, &tcp6, &["socket:[1000]", "socket:[1001]", "socket:[1002]", "/dev/null"]);
+        process(&root, 200, &tcp, &tcp6, &["socket:[1003]", "socket:[9999]"]);
+
+        fs::remove_file(root.join("200/ns/net")).unwrap();
+        fs::hard_link(root.join("100/ns/net"), root.join("200/ns/net")).unwrap();
+
+        fs::create_dir_all(root.join("self")).unwrap();
+        fs::create_dir_all(root.join("300/fd")).unwrap();
+
+        let netns = fs::metadata(root.join("100/ns/net")).unwrap().ino();
+
+        let mut sockets = sockets(&root).unwrap().into_iter().map(|socket| {
+            (socket.pid, socket.netns, socket.src, socket.dst, socket.state)
+        }).collect::<Vec<_>>();
+        sockets.sort_by_key(|socket| (socket.0, socket.2.port()));
+
+        fs::remove_dir_all(&root).ok();
+
+        assert_eq!(sockets, vec![
+            (100, netns, addr("0.0.0.0:22"), addr("0.0.0.0:0"), TcpState::Listen),
+            (100, netns, addr("10.0.0.1:22"), addr("10.0.0.2:50000"), TcpState::Established),
+            (100, netns, addr("10.0.0.1:443"), addr("10.0.0.4:58000"), TcpState::Established),
+            (200, netns, addr("10.0.0.1:8080"), addr("10.0.0.3:54321"), TcpState::Established),
+        ]);
+    }
+
+    fn process(root: &Path, pid: u32, tcp: &str, tcp6: &str, fds: &[&str]) {
+        let dir = root.join(pid.to_string());
+
+        fs::create_dir_all(dir.join("ns")).unwrap();
+        fs::create_dir_all(dir.join("net")).unwrap();
+        fs::create_dir_all(dir.join("fd")).unwrap();
+
+        fs::write(dir.join("ns/net"), "").unwrap();
+        fs::write(dir.join("net/tcp"), tcp).unwrap();
+        fs::write(dir.join("net/tcp6"), tcp6).unwrap();
+
+        for (fd, target) in fds.iter().enumerate() {
+            symlink(target, dir.join(format!("fd/{}", fd + 3))).unwrap();
+        }
+    }
+
+    fn addr(addr: &str) -> SocketAddr {
+        addr.parse().unwrap()
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+use anyhow::Result;
+use libc::pid_t;
+use log::{debug, error, warn};
+use tokio::select;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::time::interval;
+use crate::clock::monotonic;
+use crate::data::{Buildup, Errno, Proto, TcpState};
+use crate::event::{Call, Sock, State, Task};
+
+struct Conn {
+    pid:    pid_t,
+    cgroup: u64,
+    netns:  u64,
+    task:   Option<Task>,
+    src:    SocketAddr,
+    dst:    SocketAddr,
+    state:  TcpState,
+    since:  u64,
+}
+
+pub fn track<F: Future<Output = Result<Vec<Sock>>> + Send + 'static>(rx: Receiver<Sock>, states: Receiver<State>, seed: F, period: Duration) -> Receiver<Sock> {
+    let (tx, rx1) = channel(1024);
+
+    tokio::spawn(async move {
+        match exec(rx, states, seed, tx, period).await {
+            Ok(()) => debug!("state tracker finished"),
+            Err(e) => error!("state tracker failed: {:?}", e),
+        }
+    });
+
+    rx1
+}
+
+async fn exec<F: Future<Output = Result<Vec<Sock>>>>(mut rx: Receiver<Sock>, mut states: Receiver<State>, seed: F, tx: Sender<Sock>, period: Duration) -> Result<()> {
+    let mut conns    = HashMap::<u64, Conn>::new();
+    let mut last     = HashSet::<u64>::new();
+    let mut interval = interval(period);
+
+    let mut seeded   = HashMap::<(u64, SocketAddr), Conn>::new();
+
+    let sockets = seed.await.unwrap_or_else(|e| {
+        warn!("socket snapshot failed: {:?}", e);
+        Vec::new()
+    });
+
+    for sock in sockets {
+        match sock.call {
+            Call::Listen => {
+                let conn = Conn {
+                    pid:    sock.pid,
+                    cgroup: sock.cgroup,
+                    netns:  sock.netns,
+                    task:   sock.task,
+                    src:    sock.src,
+                    dst:    sock.dst,
+                    state:  TcpState::Listen,
+                    since:  sock.ts,
+                };
+                seeded.insert((conn.netns, conn.src), conn);
+            }
+            _ => tx.send(sock).await?,
+        }
+    }
+
+    loop {
+        select! {
+            sock = rx.recv() => match sock {
+                Some(sock) => tx.send(sock).await?,
+                None       => break,
+            },
+            state = states.recv() => match state {
+                Some(state) => {
+                    if state.old == TcpState::Listen || state.new == TcpState::Listen {
+                        seeded.retain(|&(netns, addr), _| {
+                            addr != state.src || (state.netns != 0 && netns != state.netns)
+                        });
+                    }
+
+                    if let Some(sock) = transition(&mut conns, state) {
+                        tx.send(sock).await?;
+                    }
+                }
+                None => break,
+            },
+            _ = interval.tick() => {
+                let now = u64::try_from(monotonic()?.as_nanos())?;
+
+                conns.retain(|_, conn| {
+                    conn.state != TcpState::TimeWait || now.saturating_sub(conn.since) < TIME_WAIT
+                });
+
+                let mut groups = HashMap::<u64, (&Conn, Buildup)>::new();
+
+                for conn in conns.values() {
+                    let (_, buildup) = groups.entry(conn.cgroup).or_insert((conn, Buildup::default()));
+                    match conn.state {
+                        TcpState::TimeWait  => buildup.time_wait  += 1,
+                        TcpState::CloseWait => buildup.close_wait += 1,
+                        _                   => (),
+                    }
+                }
+
+                groups.retain(|cgroup, (_, buildup)| {
+                    buildup.time_wait > 0 || buildup.close_wait > 0 || last.contains(cgroup)
+                });
+
+                debug!("reporting buildup for {} cgroups", groups.len());
+
+                for (conn, buildup) in groups.values() {
+                    let mut sock = sock(Call::Buildup, conn, now);
+                    sock.src     = unspecified();
+                    sock.dst     = unspecified();
+                    sock.buildup = Some(*buildup);
+                    tx.send(sock).await?;
+                }
+
+                last = groups.into_iter().filter(|(_, (_, buildup))| {
+                    buildup.time_wait > 0 || buildup.close_wait > 0
+                }).map(|(cgroup, _)| cgroup).collect();
+
+                let listeners = conns.values().filter(|conn| {
+                    conn.state == TcpState::Listen
+                }).chain(seeded.values()).collect::<Vec<_>>();
+
+                debug!("reporting {} listening sockets", listeners.len());
+
+                for conn in listeners {
+                    let mut sock = sock(Call::Listen, conn, now);
+                    sock.dst     = unspecified();
+                    tx.send(sock).await?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn transition(conns: &mut HashMap<u64, Conn>, state: State) -> Option<Sock> {
+    let conn = conns.entry(state.sk).or_insert_with(|| Conn {
+        pid:    0,
+        cgroup: 0,
+        netns:  state.netns,
+        task:   None,
+        src:    state.src,
+        dst:    state.dst,
+        state:  state.old,
+        since:  state.ts,
+    });
+
+    if state.pid != 0 {
+        conn.pid    = state.pid;
+        conn.cgroup = state.cgroup;
+        conn.task   = state.task;
+    }
+
+    conn.src = state.src;
+    conn.dst = state.dst;
+
+    let start = match state.start {
+        0     => conn.since,
+        start => start,
+    };
+
+    let sock = match (state.old, state.new) {
+        (TcpState::SynSent, TcpState::Established) => {
+            let mut sock = sock(Call::Handshake, conn, state.ts);
+            sock.handshake_latency = Some(Duration::from_nanos(state.ts.saturating_sub(start)));
+            Some(sock)
+        }
+        (TcpState::SynSent, TcpState::Close) if state.error == Some(Errno::TimedOut) => {
+            Some(sock(Call::SynTimeout, conn, state.ts))
+        }
+        _ => None,
+    };
+
+    conn.since = state.ts;
+    conn.state = match (conn.state, state.new) {
+        (TcpState::TimeWait, TcpState::Close)                          => TcpState::TimeWait,
+        (TcpState::FinWait1, TcpState::Close) if state.error.is_none() => TcpState::TimeWait,
+        (TcpState::FinWait2, TcpState::Close) if state.error.is_none() => TcpState::TimeWait,
+        (TcpState::Closing,  TcpState::Close) if state.error.is_none() => TcpState::TimeWait,
+        (_, new)                                                       => new,
+    };
+
+    if conn.state == TcpState::Close {
+        conns.remove(&state.sk);
+    }
+
+    sock
+}
+
+fn sock(call: Call, conn: &Conn, ts: u64) -> Sock {
+    Sock {
+        call, ts,
+        pid:               conn.pid,
+        cgroup:            conn.cgroup,
+        netns:             conn.netns,
+        task:              conn.task.clone(),
+        proto:             Proto::Tcp,
+        src:               conn.src,
+        dst:               conn.dst,
+        rx:                0,
+        tx:                0,
+        rx_delta:          0,
+        tx_delta:          0,
+        rx_pkts:           0,
+        tx_pkts:           0,
+        srtt:              0,
+        retx:              0,
+        error:             None,
+        handshake_latency: None,
+        buildup:           None,
+        flow:              None,
+    }
+}
+
+fn unspecified() -> SocketAddr {
+    SocketAddr::from(([0, 0, 0, 0], 0))
+}
+
+const TIME_WAIT: u64 = 60_000_000_000;
@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use parking_lot::Mutex;
+use crate::attach::Report;
+
+pub struct Stats {
+    pub hostname: Arc<String>,
+    lost:         Mutex<BTreeMap<u32, u64>>,
+    dropped:      Mutex<BTreeMap<&'static str, u64>>,
+    blocked:      Mutex<BTreeMap<&'static str, u64>>,
+    programs:     Mutex<BTreeMap<String, Option<String>>>,
+}
+
+pub struct Counter {
+    pub name:   &'static str,
+    pub labels: Vec<(&'static str, String)>,
+    pub value:  u64,
+}
+
+impl Stats {
+    pub fn new(hostname: Arc<String>) -> Self {
+        Self {
+            hostname,
+            lost:     Mutex::new(BTreeMap::new()),
+            dropped:  Mutex::new(BTreeMap::new()),
+            blocked:  Mutex::new(BTreeMap::new()),
+            programs: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn lost(&self, cpu: u32, count: u64) {
+        *self.lost.lock().entry(cpu).or_insert(0) += count;
+    }
+
+    pub fn dropped(&self, queue: &'static str) {
+        *self.dropped.lock().entry(queue).or_insert(0) += 1;
+    }
+
+    pub fn blocked(&self, queue: &'static str) {
+        *self.blocked.lock().entry(queue).or_insert(0) += 1;
+    }
+
+    pub fn programs(&self, report: &Report) {
+        let mut programs = self.programs.lock();
+
+        for attached in &report.attached {
+            programs.insert(attached.program.clone(), Some(attached.target.clone()));
+        }
+
+        for skipped in &report.skipped {
+            programs.insert(skipped.program.clone(), None);
+        }
+    }
+
+    pub fn counters(&self) -> Vec<Counter> {
+        let mut counters = Vec::new();
+
+        for (cpu, value) in self.lost.lock().iter() {
+            counters.push(Counter {
+                name:   "events_lost_total",
+                labels: vec![("cpu", cpu.to_string())],
+                value:  *value,
+            });
+        }
+
+        for (queue, value) in self.dropped.lock().iter() {
+            counters.push(Counter {
+                name:   "events_dropped_total",
+                labels: vec![("queue", queue.to_string())],
+                value:  *value,
+            });
+        }
+
+        for (queue, value) in self.blocked.lock().iter() {
+            counters.push(Counter {
+                name:   "events_blocked_total",
+                labels: vec![("queue", queue.to_string())],
+                value:  *value,
+            });
+        }
+
+        for (program, target) in self.programs.lock().iter() {
+            counters.push(Counter {
+                name:   "program_attached",
+                labels: vec![
+                    ("program", program.clone()),
+                    ("target", target.clone().unwrap_or_default()),
+                ],
+                value:  u64::from(target.is_some()),
+            });
+        }
+
+        counters
+    }
+}
@@ -1,27 +1,40 @@
 use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::fs;
 use std::future::Future;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use anyhow::{Error, Result};
 use k8s_cri::v1alpha2::ContainerStatusRequest;
 use k8s_cri::v1alpha2::runtime_service_client::RuntimeServiceClient;
 use libc::pid_t;
 use log::{debug, error};
 use parking_lot::{Mutex, RwLock};
-use procfs::{process, ProcessCgroup};
+use procfs::process;
 use shiplift::Docker;
 use tokio::net::UnixStream;
 use tokio::sync::mpsc::Receiver;
+use tokio::task::spawn_blocking;
 use tokio::time::interval;
 use tonic::transport::{Channel, Endpoint};
 use tower::service_fn;
-use crate::data::{Container, Pod, Process, Status};
-use crate::event::Exec;
+use crate::clock::monotonic;
+use crate::data::{Container, Pod, Process, Status, TcpState};
+use crate::event::{Call, Command, Exec, Sock, Task};
+use crate::snapshot;
 
 pub struct Tracker {
-    table:  RwLock<HashMap<pid_t, Arc<Process>>>,
-    client: Client,
+    table:   RwLock<HashMap<pid_t, Arc<Process>>>,
+    cgroups: RwLock<Cgroups>,
+    netns:   RwLock<HashMap<u64, Pod>>,
+    client:  Client,
+}
+
+struct Cgroups {
+    index:   HashMap<u64, String>,
+    scanned: Instant,
 }
 
 struct Client {
@@ -31,51 +44,136 @@ struct Client {
 
 impl Tracker {
     pub async fn new() -> Result<Self> {
-        let client = Client::new().await;
-        let table  = RwLock::new(HashMap::new());
-        Ok(Self { table, client })
+        let client  = Client::new().await;
+        let table   = RwLock::new(HashMap::new());
+        let cgroups = RwLock::new(spawn_blocking(Cgroups::scan).await?);
+        let netns   = RwLock::new(HashMap::new());
+        Ok(Self { table, cgroups, netns, client })
     }
 
     pub fn spawn(self: Arc<Self>, rx: Receiver<Exec>) {
+        spawn(self.clone().seed());
         spawn(self.clone().recv(rx));
         spawn(self.sweep());
     }
 
-    pub async fn get(&self, pid: pid_t) -> Option<Arc<Process>> {
+    pub async fn get(&self, pid: pid_t, cgroup: u64, task: Option<&Task>) -> Arc<Process> {
+        if pid == 0 {
+            return self.orphan(pid, cgroup, task).await;
+        }
+
         let process = self.table.read().get(&pid).cloned();
 
         if let Some(process) = process {
-            return Some(process);
+            return process;
         }
 
-        let process = self.lookup(pid).await?;
-        self.table.write().insert(pid, process)
+        let process = match self.lookup(pid).await {
+            Some(process) => process,
+            None          => self.orphan(pid, cgroup, task).await,
+        };
+
+        self.table.write().insert(pid, process.clone());
+
+        process
+    }
+
+    pub fn pod(&self, netns: u64) -> Option<Pod> {
+        self.netns.read().get(&netns).cloned()
+    }
+
+    async fn seed(self: Arc<Self>) -> Result<()> {
+        let procs = process::all_processes()?;
+
+        for proc in &procs {
+            if let Some(process) = self.lookup(proc.pid).await {
+                self.table.write().entry(proc.pid).or_insert(process);
+            }
+        }
+
+        debug!("seeded {} processes", procs.len());
+
+        Ok(())
+    }
+
+    pub async fn sockets() -> Result<Vec<Sock>> {
+        let root    = Path::new("/proc");
+        let sockets = spawn_blocking(move || snapshot::sockets(root)).await??;
+        let ts      = u64::try_from(monotonic()?.as_nanos())?;
+
+        Ok(sockets.into_iter().filter_map(|socket| match socket.state {
+            TcpState::Listen      => Some(socket.sock(Call::Listen, ts)),
+            TcpState::Established => Some(socket.sock(Call::Established, ts)),
+            _                     => None,
+        }).collect())
     }
 
     async fn recv(self: Arc<Self>, mut rx: Receiver<Exec>) -> Result<()> {
         while let Some(e) = rx.recv().await {
             match e {
-                Exec::Exec(pid) => self.exec(pid).await,
-                Exec::Exit(pid) => self.exit(pid).await,
+                Exec::Exec(pid, command) => self.exec(pid, command).await,
+                Exec::Fork(pid, ppid)    => self.fork(pid, ppid).await,
+                Exec::Exit(pid)          => self.exit(pid).await,
             }
         }
         Ok(())
     }
 
-    async fn exec(&self, pid: pid_t) {
-        if let Some(process) = self.lookup(pid).await {
-            self.table.write().insert(pid, process);
+    async fn exec(&self, pid: pid_t, command: Option<Command>) {
+        let process = match (self.lookup(pid).await, command) {
+            (Some(process), Some(command)) => Arc::new(fill(&process, command)),
+            (Some(process), None)          => process,
+            (None, Some(command))          => {
+                let process = self.orphan(pid, command.cgroup, command.task.as_ref()).await;
+                self.inherit(fill(&process, command))
+            }
+            (None, None)                   => return,
+        };
+
+        self.table.write().insert(pid, process);
+    }
+
+    async fn fork(&self, pid: pid_t, ppid: pid_t) {
+        let parent = self.table.read().get(&ppid).cloned();
+
+        let process = match parent {
+            Some(parent) => Arc::new(Process {
+                pid,
+                ppid:   Some(ppid),
+                status: Status::Alive,
+                ..Process::clone(&parent)
+            }),
+            None => match self.lookup(pid).await {
+                Some(process) => process,
+                None          => return,
+            },
+        };
+
+        let mut table = self.table.write();
+        if !matches!(table.get(&pid), Some(p) if p.status == Status::Alive && p.ppid == Some(ppid)) {
+            table.insert(pid, process);
         }
     }
 
+    pub fn ancestry(&self, process: &Process, depth: usize) -> Vec<Arc<Process>> {
+        let table = self.table.read();
+        let mut ancestry = Vec::new();
+        let mut ppid = process.ppid;
+
+        while let Some(parent) = ppid.filter(|_| ancestry.len() < depth).and_then(|pid| table.get(&pid)) {
+            ppid = parent.ppid.filter(|&ppid| ppid != parent.pid);
+            ancestry.push(parent.clone());
+        }
+
+        ancestry
+    }
+
     async fn exit(&self, pid: pid_t) {
         self.table.write().entry(pid).and_modify(|p| {
             *p = Arc::new(Process {
-                pid:       p.pid,
-                command:   p.command.clone(),
-                container: p.container.clone(),
-                status:    Status::Dead,
-                pod:       None,
+                status: Status::Dead,
+                pod:    None,
+                ..Process::clone(p)
             });
         });
     }
@@ -83,21 +181,107 @@ impl Tracker {
     async fn lookup(&self, pid: pid_t) -> Option<Arc<Process>> {
         let proc    = process::Process::new(pid).ok()?;
         let command = proc.cmdline().ok()?;
+        let exe     = proc.exe().ok().map(|p| p.display().to_string());
+        let ppid    = Some(proc.stat.ppid);
         let cgroups = proc.cgroups().ok()?;
+        let creds   = proc.status().ok();
+        let comm    = proc.stat.comm.clone();
+        let netns   = fs::metadata(format!("/proc/{}/ns/net", pid)).ok().map(|m| m.ino());
+        let uid     = creds.as_ref().map(|s| s.ruid);
+        let gid     = creds.as_ref().map(|s| s.rgid);
         let status  = Status::Alive;
 
         let mut container = None;
         let mut pod = None;
 
         for cgroup in &cgroups {
-            if let Some(c) = self.client.lookup(cgroup).await {
+            if let Some(c) = self.client.lookup(&cgroup.pathname).await {
                 pod = self.client.pod(&c).await;
                 container = Some(c);
                 break;
             }
         }
 
-        Some(Arc::new(Process { pid, command, container, pod, status }))
+        if let (Some(netns), Some(pod)) = (netns, &pod) {
+            self.netns.write().insert(netns, pod.clone());
+        }
+
+        Some(Arc::new(Process {
+            pid:       pid,
+            ppid:      ppid,
+            command:   command,
+            exe:       exe,
+            comm:      comm,
+            netns:     netns,
+            uid:       uid,
+            gid:       gid,
+            container: container,
+            pod:       pod,
+            status:    status,
+            resolved:  true,
+        }))
+    }
+
+    async fn orphan(&self, pid: pid_t, cgroup: u64, task: Option<&Task>) -> Arc<Process> {
+        let mut container = None;
+        let mut pod = None;
+
+        if let Some(path) = self.cgroup(cgroup).await {
+            if let Some(c) = self.client.lookup(&path).await {
+                debug!("pid {} attributed via cgroup {}", pid, path);
+                pod = self.client.pod(&c).await;
+                container = Some(c);
+            }
+        }
+
+        Arc::new(Process {
+            pid:       pid,
+            ppid:      None,
+            command:   Vec::new(),
+            exe:       None,
+            comm:      task.map(|t| t.comm.clone()).unwrap_or_default(),
+            netns:     None,
+            uid:       task.map(|t| t.uid),
+            gid:       task.map(|t| t.gid),
+            container: container,
+            pod:       pod,
+            status:    Status::Dead,
+            resolved:  false,
+        })
+    }
+
+    fn inherit(&self, mut process: Process) -> Arc<Process> {
+        let parent = process.ppid.and_then(|ppid| self.table.read().get(&ppid).cloned());
+
+        if let Some(parent) = parent.filter(|_| process.container.is_none()) {
+            debug!("pid {} inherited container from {}", process.pid, parent.pid);
+            process.container = parent.container.clone();
+            process.pod       = parent.pod.clone();
+        }
+
+        Arc::new(process)
+    }
+
+    async fn cgroup(&self, id: u64) -> Option<String> {
+        {
+            let mut cgroups = self.cgroups.write();
+
+            if let Some(path) = cgroups.index.get(&id) {
+                return Some(path.clone());
+            }
+
+            if id == 0 || cgroups.scanned.elapsed() < RESCAN {
+                return None;
+            }
+
+            cgroups.scanned = Instant::now();
+        }
+
+        let cgroups = spawn_blocking(Cgroups::scan).await.ok()?;
+        let path = cgroups.index.get(&id).cloned();
+        *self.cgroups.write() = cgroups;
+
+        path
     }
 
     async fn sweep(self: Arc<Self>) -> Result<()> {
@@ -112,6 +296,17 @@ impl Tracker {
             table.retain(|_, p| p.status == Status::Alive);
 
             debug!("swept {} dead processes", n - table.len());
+
+            let netns = table.values().filter_map(|p| {
+                Some((p.netns?, p.pod.clone()?))
+            }).collect();
+
+            drop(table);
+
+            *self.netns.write() = netns;
+
+            let cgroups = spawn_blocking(Cgroups::scan).await?;
+            *self.cgroups.write() = cgroups;
         }
     }
 }
@@ -132,8 +327,8 @@ impl Client {
         Self { docker, kube }
     }
 
-    async fn lookup(&self, cgroup: &ProcessCgroup) -> Option<Container> {
-        match cgroup.pathname.split('/').collect::<Vec<_>>()[..] {
+    async fn lookup(&self, cgroup: &str) -> Option<Container> {
+        match cgroup.split('/').collect::<Vec<_>>()[..] {
             ["", "docker",       id] => self.docker(id).await,
             ["", "kubepods", .., id] => self.kube(id).await,
             _                        => None,
@@ -175,6 +370,67 @@ impl Client {
     }
 }
 
+impl Cgroups {
+    fn scan() -> Self {
+        let roots = ["/sys/fs/cgroup/unified", "/sys/fs/cgroup"];
+        let root  = roots.iter().map(Path::new).find(|path| {
+            path.join("cgroup.procs").exists()
+        });
+
+        let mut index = HashMap::new();
+
+        if let Some(root) = root {
+            walk(root, root, &mut index);
+        }
+
+        debug!("indexed {} cgroups", index.len());
+
+        Self { index, scanned: Instant::now() }
+    }
+}
+
+fn walk(root: &Path, dir: &Path, index: &mut HashMap<u64, String>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_)      => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if let Ok(meta) = entry.metadata() {
+            if meta.is_dir() {
+                if let Ok(name) = path.strip_prefix(root) {
+                    index.insert(meta.ino(), format!("/{}", name.display()));
+                }
+                walk(root, &path, index);
+            }
+        }
+    }
+}
+
+fn fill(process: &Process, command: Command) -> Process {
+    let mut process = process.clone();
+
+    if process.command.is_empty() {
+        process.command = command.argv;
+    }
+
+    if process.exe.is_none() && !command.filename.is_empty() {
+        process.exe = Some(command.filename);
+    }
+
+    process.ppid.get_or_insert(command.ppid);
+
+    if command.netns != 0 {
+        process.netns.get_or_insert(command.netns);
+    }
+
+    process
+}
+
+const RESCAN: Duration = Duration::from_secs(1);
+
 fn spawn<F: Future<Output = Result<()>> + Send + 'static>(task: F) {
     tokio::spawn(async move {
         match task.await {
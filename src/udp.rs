@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use anyhow::Result;
+use libc::pid_t;
+use log::{debug, error};
+use tokio::select;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::time::interval;
+use crate::code::UdpMap;
+use crate::event::Sock;
+
+type Key = (pid_t, SocketAddr, SocketAddr);
+
+type Seen = (u64, u64, u64, u32, u32);
+
+pub fn aggregate(rx: Receiver<Sock>, map: Option<UdpMap>, period: Duration) -> Receiver<Sock> {
+    let map = match map {
+        Some(map) => map,
+        None      => return rx,
+    };
+
+    let (tx, rx1) = channel(1024);
+
+    tokio::spawn(async move {
+        match exec(rx, tx, map, period).await {
+            Ok(()) => debug!("aggregator finished"),
+            Err(e) => error!("aggregator failed: {:?}", e),
+        }
+    });
+
+    rx1
+}
+
+async fn exec(mut rx: Receiver<Sock>, tx: Sender<Sock>, mut map: UdpMap, period: Duration) -> Result<()> {
+    let mut last     = HashMap::<Key, Seen>::new();
+    let mut interval = interval(period);
+
+    loop {
+        select! {
+            sock = rx.recv() => match sock {
+                Some(sock) => tx.send(sock).await?,
+                None       => break,
+            },
+            _ = interval.tick() => {
+                let socks = drain(&mut map, &mut last)?;
+
+                debug!("flushing {} udp flows", socks.len());
+
+                for sock in socks {
+                    tx.send(sock).await?;
+                }
+            }
+        }
+    }
+
+    for sock in drain(&mut map, &mut last)? {
+        tx.send(sock).await?;
+    }
+
+    Ok(())
+}
+
+fn drain(map: &mut UdpMap, last: &mut HashMap<Key, Seen>) -> Result<Vec<Sock>> {
+    let entries = unsafe { map.iter() }.collect::<Result<Vec<_>, _>>()?;
+    let mut socks = Vec::new();
+
+    for (key, flow) in entries {
+        let mut sock = Sock::udp(&key, &flow)?;
+        let id = (sock.pid, sock.src, sock.dst);
+        let (ts, rx, tx, rx_pkts, tx_pkts) = last.get(&id).copied().unwrap_or_default();
+
+        if sock.ts == ts {
+            map.remove(&key)?;
+            last.remove(&id);
+            continue;
+        }
+
+        last.insert(id, (sock.ts, sock.rx, sock.tx, sock.rx_pkts, sock.tx_pkts));
+
+        sock.rx       = sock.rx.saturating_sub(rx);
+        sock.tx       = sock.tx.saturating_sub(tx);
+        sock.rx_delta = sock.rx;
+        sock.tx_delta = sock.tx;
+        sock.rx_pkts  = sock.rx_pkts.wrapping_sub(rx_pkts);
+        sock.tx_pkts  = sock.tx_pkts.wrapping_sub(tx_pkts);
+
+        socks.push(sock);
+    }
+
+    Ok(socks)
+}